@@ -1,5 +1,9 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
+use std::error::Error;
 use std::fmt;
 use std::fs;
+use std::mem;
 use std::time::Instant;
 
 #[derive(PartialEq, Debug)]
@@ -25,102 +29,276 @@ enum Step {
     Left(i32),
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
 struct Point {
     x: i32,
     y: i32,
 }
 
+// Position is 1-based, the column points at the offending character.
+#[derive(PartialEq, Debug)]
+struct ParseError {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+impl ParseError {
+    fn new(column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line: 1,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for ParseError {}
+
 #[derive(PartialEq, Debug)]
 struct Wire(Vec<Segment>);
 
 impl Wire {
-    pub fn parse(input: &str) -> Self {
-        let mut result = vec![];
+    // Zero-length steps are dropped and steps going on in the same direction
+    // are merged, so a crossing never falls on the joint of two segments.
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let mut result: Vec<Segment> = vec![];
+        let mut last: Option<Step> = None;
         let mut x = 0;
         let mut y = 0;
+        let mut offset = 0;
 
-        input.split(',').for_each(|step| {
-            let step = Step::parse(step);
-            let (x1, y1) = step.next(x, y);
-            result.push(Segment::new(x, y, x1, y1));
+        for step in input.trim_end_matches(['\n', '\r']).split(',') {
+            let parsed = Step::parse(step).map_err(|e| ParseError {
+                column: e.column + offset,
+                ..e
+            })?;
+            offset += step.chars().count() + 1;
 
+            if parsed.len() == 0 {
+                continue;
+            }
+
+            let (x1, y1) = parsed.next(x, y);
+            let same = last
+                .as_ref()
+                .is_some_and(|l| mem::discriminant(l) == mem::discriminant(&parsed));
+            match result.last_mut() {
+                Some(segment) if same => *segment = Segment::new(segment.x0, segment.y0, x1, y1),
+                _ => result.push(Segment::new(x, y, x1, y1)),
+            }
+
+            last = Some(parsed);
             x = x1;
             y = y1;
-        });
-        Self { 0: result }
-    }
+        }
+
+        Ok(Self { 0: result })
+    }
+
+    // Deterministic random walk of `steps` steps up to `max_len` long, from
+    // the same seed always the same wire.
+    pub fn random(steps: usize, max_len: u64, seed: u64) -> Self {
+        let mut state = seed;
+        let steps = (0..steps)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                let len = (state >> 33) % max_len + 1;
+                let dir = ["U", "R", "D", "L"][(state >> 60) as usize % 4];
+                format!("{}{}", dir, len)
+            })
+            .collect::<Vec<_>>();
+
+        Self::parse(&steps.join(",")).expect("generated wire")
+    }
+
+    pub fn end(&self) -> Point {
+        self.0
+            .last()
+            .map_or(Point::new(0, 0), |s| Point::new(s.x1, s.y1))
+    }
+
+    // Steps to the first visit of every point in `points` the wire passes.
+    // Points are looked up by row and column, so the wire is walked once.
+    pub fn steps_to(&self, points: &[Point]) -> HashMap<Point, i32> {
+        let mut rows: HashMap<i32, BTreeSet<i32>> = HashMap::new();
+        let mut cols: HashMap<i32, BTreeSet<i32>> = HashMap::new();
+        for p in points {
+            rows.entry(p.y).or_default().insert(p.x);
+            cols.entry(p.x).or_default().insert(p.y);
+        }
 
-    pub fn steps_to(&self, p: &Point) -> Option<i32> {
+        let mut result = HashMap::new();
         let mut current = 0;
 
         for step in self.0.iter() {
-            if step.constain(p) {
-                return Some(current + step.start().distance_to(p));
-            } else {
-                current += step.lenght();
+            let hits = match step.dir {
+                Direction::Horizontal => rows.get(&step.y0).map(|xs| {
+                    xs.range(step.x_min()..=step.x_max())
+                        .map(|x| Point::new(*x, step.y0))
+                        .collect::<Vec<_>>()
+                }),
+                Direction::Vertical => cols.get(&step.x0).map(|ys| {
+                    ys.range(step.y_min()..=step.y_max())
+                        .map(|y| Point::new(step.x0, *y))
+                        .collect::<Vec<_>>()
+                }),
+            };
+
+            for p in hits.unwrap_or_default() {
+                result
+                    .entry(p)
+                    .or_insert_with(|| current + step.start().distance_to(&p));
             }
+
+            current += step.lenght();
         }
 
-        None
+        result
     }
 }
 
-// Board - 2 set of segments
+// Point where two or more wires cross and every wire going through it.
+#[derive(PartialEq, Debug)]
+struct Crossing {
+    point: Point,
+    wires: Vec<usize>,
+}
+
+// Board - any number of wires
 #[derive(PartialEq, Debug)]
 struct Board {
-    a: Wire,
-    b: Wire,
-    crossing: Vec<Point>,
+    wires: Vec<Wire>,
+    crossing: Vec<Crossing>,
 }
 
 impl Board {
-    fn new(a: Wire, b: Wire) -> Self {
-        let crossing =
-            a.0.iter()
-                .map(|a| b.0.iter().filter_map(move |b| cross(a, b)))
-                .flatten()
-                .collect::<Vec<_>>();
+    fn new(wires: Vec<Wire>) -> Self {
+        // the central port doesn't count even if a wire comes back to it, a
+        // wire ending on another one only touches it
+        let mut points: BTreeMap<Point, BTreeSet<usize>> = BTreeMap::new();
+        let shared = sweep::crossings(&wires)
+            .into_iter()
+            .chain(sweep::overlaps(&wires));
+        for (point, a, b) in shared {
+            if point == Point::new(0, 0) || point == wires[a].end() || point == wires[b].end() {
+                continue;
+            }
+
+            let entry = points.entry(point).or_default();
+            entry.insert(a);
+            entry.insert(b);
+        }
+
+        let crossing = points
+            .into_iter()
+            .map(|(point, wires)| Crossing {
+                point,
+                wires: wires.into_iter().collect(),
+            })
+            .collect();
 
-        Self { a, b, crossing }
+        Self { wires, crossing }
     }
 
-    pub fn parse(input: &str) -> Self {
-        let mut wires = input.lines().map(|line| Wire::parse(line));
-        let a = wires.next().expect("invalid input");
-        let b = wires.next().expect("invalid input");
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let wires = input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| Wire::parse(line).map_err(|e| ParseError { line: i + 1, ..e }))
+            .collect::<Result<_, _>>()?;
 
-        Self::new(a, b)
+        Ok(Self::new(wires))
+    }
+
+    // Every pair of wires meeting at each crossing.
+    pub fn pairs(&self) -> impl Iterator<Item = (usize, usize, Point)> + '_ {
+        self.crossing.iter().flat_map(|c| {
+            c.wires
+                .iter()
+                .enumerate()
+                .flat_map(move |(i, a)| c.wires[i + 1..].iter().map(move |b| (*a, *b, c.point)))
+        })
+    }
+
+    // Crossings of at least `k` wires.
+    pub fn k_way(&self, k: usize) -> impl Iterator<Item = &Crossing> {
+        self.crossing.iter().filter(move |c| c.wires.len() >= k)
+    }
+
+    pub fn closest_cross(&self) -> Option<&Crossing> {
+        self.crossing.iter().min_by_key(|c| c.point.distance())
     }
 
     pub fn cross_distance(&self) -> Option<i32> {
-        self.crossing.iter().map(|point| point.distance()).min()
+        self.closest_cross().map(|c| c.point.distance())
     }
 
-    pub fn step_distance(&self) -> Option<i32> {
+    // Crossing with the fewest combined steps of all wires going through it.
+    pub fn closest_steps(&self) -> Option<(&Crossing, i32)> {
+        let points = self.crossing.iter().map(|c| c.point).collect::<Vec<_>>();
+        let steps = self
+            .wires
+            .iter()
+            .map(|wire| wire.steps_to(&points))
+            .collect::<Vec<_>>();
+
         self.crossing
             .iter()
-            .filter_map(
-                |point| match (self.a.steps_to(&point), self.b.steps_to(&point)) {
-                    (Some(a), Some(b)) => Some(a + b),
-                    _ => None,
-                },
-            )
-            .min()
+            .filter_map(|c| {
+                let total = c
+                    .wires
+                    .iter()
+                    .map(|w| steps[*w].get(&c.point))
+                    .sum::<Option<i32>>()?;
+                Some((c, total))
+            })
+            .min_by_key(|(_, total)| *total)
+    }
+
+    pub fn step_distance(&self) -> Option<i32> {
+        self.closest_steps().map(|(_, total)| total)
     }
 }
 
 impl Step {
-    fn parse(input: &str) -> Self {
-        let dir = input.chars().nth(0).unwrap();
-        let len = &input[1..].trim_matches('\n').parse::<i32>().unwrap();
-
-        match dir {
-            'U' => Self::Up(*len),
-            'R' => Self::Right(*len),
-            'D' => Self::Down(*len),
-            'L' => Self::Left(*len),
-            _ => panic!("wrong direction"),
+    fn parse(input: &str) -> Result<Self, ParseError> {
+        let mut chars = input.chars();
+        let dir = chars
+            .next()
+            .ok_or_else(|| ParseError::new(1, "empty step"))?;
+
+        let step: fn(i32) -> Self = match dir {
+            'U' => Self::Up,
+            'R' => Self::Right,
+            'D' => Self::Down,
+            'L' => Self::Left,
+            _ => return Err(ParseError::new(1, format!("wrong direction {:?}", dir))),
+        };
+
+        let len = chars.as_str().trim_end_matches('\n');
+        match len.parse::<i32>() {
+            Ok(len) if len >= 0 => Ok(step(len)),
+            _ => Err(ParseError::new(2, format!("invalid length {:?}", len))),
+        }
+    }
+
+    fn len(&self) -> i32 {
+        match self {
+            Self::Up(i) | Self::Right(i) | Self::Down(i) | Self::Left(i) => *i,
         }
     }
 
@@ -135,10 +313,11 @@ impl Step {
 }
 
 impl Segment {
+    // A zero-length segment is a horizontal one with both ends at the same x.
     fn new(x0: i32, y0: i32, x1: i32, y1: i32) -> Segment {
         let dir = match (x0, x1, y0, y1) {
             (x0, x1, y0, y1) if x0 == x1 && y0 != y1 => Direction::Vertical,
-            (x0, x1, y0, y1) if x0 != x1 && y0 == y1 => Direction::Horizontal,
+            (_, _, y0, y1) if y0 == y1 => Direction::Horizontal,
             _ => panic!("invalid segment"),
         };
 
@@ -151,13 +330,6 @@ impl Segment {
         }
     }
 
-    pub fn constain(&self, p: &Point) -> bool {
-        match self.dir {
-            Direction::Horizontal => p.x >= self.x_min() && p.x <= self.x_max() && p.y == self.y0,
-            Direction::Vertical => p.y >= self.y_min() && p.y <= self.y_max() && p.x == self.x0,
-        }
-    }
-
     pub fn start(&self) -> Point {
         Point {
             x: self.x0,
@@ -234,9 +406,30 @@ impl Point {
     }
 }
 
-fn cross<'a>(a: &'a Segment, b: &'a Segment) -> Option<Point> {
+// Every point of a collinear overlap longer than zero.
+fn overlap(a: &Segment, b: &Segment) -> Vec<Point> {
+    match a.dir {
+        Direction::Horizontal if a.y0 == b.y0 => {
+            let (low, high) = (a.x_min().max(b.x_min()), a.x_max().min(b.x_max()));
+            if low < high {
+                return (low..=high).map(|x| Point::new(x, a.y0)).collect();
+            }
+        }
+        Direction::Vertical if a.x0 == b.x0 => {
+            let (low, high) = (a.y_min().max(b.y_min()), a.y_max().min(b.y_max()));
+            if low < high {
+                return (low..=high).map(|y| Point::new(a.x0, y)).collect();
+            }
+        }
+        _ => {}
+    }
+
+    vec![]
+}
+
+fn cross<'a>(a: &'a Segment, b: &'a Segment) -> Vec<Point> {
     if a.dir == b.dir {
-        return None;
+        return overlap(a, b);
     }
 
     let (a, b) = match (a, b) {
@@ -252,18 +445,70 @@ fn cross<'a>(a: &'a Segment, b: &'a Segment) -> Option<Point> {
     let y_min = b.y0.min(b.y1);
     let y_max = b.y0.max(b.y1);
 
-    if x_min < b.x0 && x_max > b.x0 && y_min < a.y0 && y_max > a.y0 {
-        Some(Point::new(b.x0, a.y0))
+    // ends count too, a wire may turn right where the other one passes
+    if x_min <= b.x0 && x_max >= b.x0 && y_min <= a.y0 && y_max >= a.y0 {
+        vec![Point::new(b.x0, a.y0)]
     } else {
-        None
+        vec![]
     }
 }
 
-fn main() {
+// Random walks with `segments` steps each, compared with checking every
+// pair of segments while that is still feasible.
+fn bench(segments: usize, wires: usize) {
+    let wires = (0..wires)
+        .map(|i| Wire::random(segments, 100, 3 + i as u64))
+        .collect::<Vec<_>>();
+
+    println!("{} wires, {} segments each", wires.len(), segments);
+
+    if segments <= 10_000 {
+        let start = Instant::now();
+        let mut count = 0;
+        for (i, a) in wires.iter().enumerate() {
+            for b in wires[i + 1..].iter() {
+                for sa in a.0.iter() {
+                    count += b.0.iter().map(|sb| cross(sa, sb).len()).sum::<usize>();
+                }
+            }
+        }
+        println!("  pairwise: {} crossings in {:?}", count, start.elapsed());
+    }
+
+    let start = Instant::now();
+    let count = sweep::crossings(&wires).len() + sweep::overlaps(&wires).len();
+    println!("  sweep   : {} crossings in {:?}", count, start.elapsed());
+
+    let start = Instant::now();
+    let board = Board::new(wires);
+    let steps = board.step_distance();
+    println!("  board   : {:?} steps in {:?}", steps, start.elapsed());
+}
+
+mod svg;
+mod sweep;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = env::args().collect::<Vec<_>>();
+    if args.get(1).map(String::as_str) == Some("bench") {
+        let segments = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(100_000);
+        let wires = args.get(3).and_then(|s| s.parse().ok()).unwrap_or(3);
+        bench(segments, wires);
+        return Ok(());
+    }
+
     let now = Instant::now();
 
-    let input = fs::read_to_string("input.txt").expect("input.txt not found");
-    let board = Board::parse(&input);
+    let input = fs::read_to_string("input.txt")?;
+    let board = Board::parse(&input).map_err(|e| format!("input.txt: {}", e))?;
+
+    // day03 svg [file]
+    if args.get(1).map(String::as_str) == Some("svg") {
+        let path = args.get(2).map(String::as_str).unwrap_or("board.svg");
+        fs::write(path, board.to_svg())?;
+        println!("{} wires written to {}", board.wires.len(), path);
+        return Ok(());
+    }
 
     let task_a = board.cross_distance();
     let task_b = board.step_distance();
@@ -280,7 +525,13 @@ fn main() {
         _ => println!("Q1: Not found"),
     }
 
+    println!("Pairwise crossings: {}", board.pairs().count());
+    for k in 3..=board.wires.len() {
+        println!("{}-way crossings: {}", k, board.k_way(k).count());
+    }
+
     println!("Total time: {}μs", total_time.as_micros());
+    Ok(())
 }
 
 #[cfg(test)]
@@ -289,8 +540,79 @@ mod test {
 
     #[test]
     fn test_parse_step() {
-        assert_eq!(Step::parse("U10"), Step::Up(10));
-        assert_eq!(Step::parse("L11\n"), Step::Left(11));
+        assert_eq!(Step::parse("U10"), Ok(Step::Up(10)));
+        assert_eq!(Step::parse("L11\n"), Ok(Step::Left(11)));
+        assert_eq!(Step::parse("R0"), Ok(Step::Right(0)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input| Board::parse(input).unwrap_err();
+
+        assert_eq!(
+            ParseError {
+                line: 2,
+                column: 1,
+                message: "empty step".to_owned()
+            },
+            error("R1,U2\n,R3")
+        );
+        assert_eq!(
+            ParseError {
+                line: 2,
+                column: 5,
+                message: "wrong direction 'X'".to_owned()
+            },
+            error("R1,U2\nR10,X3")
+        );
+        assert_eq!(
+            "line 1, column 8: invalid length \"1a\"",
+            error("R1,U2,D1a").to_string()
+        );
+        assert_eq!(
+            "line 1, column 5: invalid length \"-2\"",
+            error("R1,U-2").to_string()
+        );
+        assert_eq!(
+            "line 3, column 8: empty step",
+            error("R1\n\nR1,U22,").to_string()
+        );
+        assert_eq!(
+            "line 1, column 2: invalid length \"\"",
+            error("R").to_string()
+        );
+    }
+
+    #[test]
+    fn test_zero_length() {
+        let wire = Wire::parse("R0,U3,L0,R5").unwrap();
+        assert_eq!(
+            vec![Segment::new(0, 0, 0, 3), Segment::new(0, 3, 5, 3)],
+            wire.0
+        );
+
+        let wire = Wire::parse("U3,U2,R0,U1,D4").unwrap();
+        assert_eq!(
+            vec![Segment::new(0, 0, 0, 6), Segment::new(0, 6, 0, 2)],
+            wire.0
+        );
+
+        let board = Board::parse("R0,U3,L0,R5\nR2,U5").unwrap();
+        assert_eq!(Some(5), board.cross_distance());
+        assert_eq!(Some(10), board.step_distance());
+    }
+
+    #[test]
+    fn test_overlapping() {
+        // the wires share (2,0)..(5,0) and nothing else
+        let board = Board::parse("R5,U1\nU1,R2,D1,R6").unwrap();
+        assert_eq!(Some(2), board.cross_distance());
+        assert_eq!(Some(2 + 4), board.step_distance());
+        assert_eq!(4, board.pairs().count());
+
+        // a point shared at the end only is not an overlap
+        let board = Board::parse("R5\nU1,R5,D1,R5").unwrap();
+        assert_eq!(None, board.cross_distance());
     }
 
     #[test]
@@ -322,7 +644,7 @@ mod test {
     #[test]
     fn test_build() {
         let input = "R10,U1\n";
-        let result = Wire::parse(input);
+        let result = Wire::parse(input).unwrap();
         let expected = Wire {
             0: vec![Segment::new(0, 0, 10, 0), Segment::new(10, 0, 10, 1)],
         };
@@ -333,15 +655,19 @@ mod test {
     #[test]
     fn test_cross() {
         assert_eq!(
-            None,
+            (0..=5).map(|x| Point::new(x, 0)).collect::<Vec<_>>(),
             cross(&Segment::new(0, 0, 10, 0), &Segment::new(0, 0, 5, 0))
         );
         assert_eq!(
-            Some(Point::new(5, 0)),
+            Vec::<Point>::new(),
+            cross(&Segment::new(0, 0, 10, 0), &Segment::new(10, 0, 15, 0))
+        );
+        assert_eq!(
+            vec![Point::new(5, 0)],
             cross(&Segment::new(0, 0, 10, 0), &Segment::new(5, 5, 5, -5))
         );
         assert_eq!(
-            Some(Point::new(2, 1)),
+            vec![Point::new(2, 1)],
             cross(&Segment::new(2, 2, 2, -2), &Segment::new(3, 1, 1, 1))
         );
     }
@@ -349,21 +675,81 @@ mod test {
     #[test]
     fn test_cross_distance_1() {
         let input = "R8,U5,L5,D3\nU7,R6,D4,L4";
-        let board = Board::parse(input);
+        let board = Board::parse(input).unwrap();
         assert_eq!(Some(6), board.cross_distance());
     }
 
     #[test]
     fn test_cross_distance_2() {
         let input = "R75,D30,R83,U83,L12,D49,R71,U7,L72\nU62,R66,U55,R34,D71,R55,D58,R83";
-        let board = Board::parse(input);
+        let board = Board::parse(input).unwrap();
         assert_eq!(Some(159), board.cross_distance());
     }
+    #[test]
+    fn test_step_distance() {
+        let input = "R8,U5,L5,D3\nU7,R6,D4,L4";
+        assert_eq!(Some(30), Board::parse(input).unwrap().step_distance());
+
+        let input = "R75,D30,R83,U83,L12,D49,R71,U7,L72\nU62,R66,U55,R34,D71,R55,D58,R83";
+        assert_eq!(Some(610), Board::parse(input).unwrap().step_distance());
+    }
+
+    #[test]
+    fn test_steps_to_first_visit() {
+        let wire = Wire::parse("R5,U5,L2,D10").unwrap();
+        let steps = wire.steps_to(&[Point::new(3, 0), Point::new(3, 5), Point::new(9, 9)]);
+
+        assert_eq!(Some(&3), steps.get(&Point::new(3, 0)));
+        assert_eq!(Some(&12), steps.get(&Point::new(3, 5)));
+        assert_eq!(None, steps.get(&Point::new(9, 9)));
+    }
+
+    #[test]
+    fn test_corners() {
+        // both wires turn at (5,0) and pass through each other there
+        let board = Board::parse("R5,U5\nD3,R5,U3,R3").unwrap();
+        assert_eq!(Some(5), board.cross_distance());
+        assert_eq!(
+            vec![(0, 1, Point::new(5, 0))],
+            board.pairs().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(5 + 11), board.step_distance());
+
+        // one wire turns where the other one goes straight
+        let board = Board::parse("R5,U5\nD3,R5,U6").unwrap();
+        assert_eq!(Some(5), board.cross_distance());
+    }
+
+    #[test]
+    fn test_many_wires() {
+        let input = "R8,U5,L5,D3\nU7,R6,D4,L4\nL1,U3,R11\nD1,R4,U9";
+        let board = Board::parse(input).unwrap();
+
+        assert_eq!(4, board.wires.len());
+        assert_eq!(Some(3), board.cross_distance());
+
+        // the third wire runs along the second one from (2,3) to (6,3) and
+        // through (3,3) where the first two cross, the fourth one crosses
+        // both of them at (4,3)
+        let triple = board.k_way(3).collect::<Vec<_>>();
+        assert_eq!(2, triple.len());
+        assert_eq!(Point::new(3, 3), triple[0].point);
+        assert_eq!(vec![0, 1, 2], triple[0].wires);
+        assert_eq!(Point::new(4, 3), triple[1].point);
+        assert_eq!(vec![1, 2, 3], triple[1].wires);
+
+        let pairs = board.pairs().collect::<Vec<_>>();
+        assert!(pairs.contains(&(0, 2, Point::new(3, 3))));
+        assert!(pairs.contains(&(1, 2, Point::new(3, 3))));
+        assert!(pairs.contains(&(0, 3, Point::new(4, 5))));
+        assert_eq!(0, board.k_way(4).count());
+    }
+
     #[test]
     fn test_cross_distance_3() {
         let input =
             "R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51\nU98,R91,D20,R16,D67,R40,U7,R15,U6,R7";
-        let board = Board::parse(input);
+        let board = Board::parse(input).unwrap();
         assert_eq!(Some(135), board.cross_distance());
     }
 }
@@ -0,0 +1,192 @@
+use crate::{Board, Point};
+use std::fmt::Write;
+
+const SIZE: f64 = 1000.0;
+const MARGIN: f64 = 40.0;
+const COLORS: [&str; 6] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#9467bd", "#8c564b", "#e377c2",
+];
+const MANHATTAN: &str = "#d62728";
+const STEPS: &str = "#17becf";
+
+// Maps board coordinates into the picture keeping the aspect ratio, up on
+// the board is up in the picture.
+struct Frame {
+    min_x: i32,
+    max_y: i32,
+    scale: f64,
+}
+
+impl Frame {
+    fn of(board: &Board) -> Self {
+        let points = board
+            .wires
+            .iter()
+            .flat_map(|w| w.0.iter())
+            .flat_map(|s| [(s.x0, s.y0), (s.x1, s.y1)]);
+
+        let (mut min_x, mut max_x, mut min_y, mut max_y) = (0, 0, 0, 0);
+        for (x, y) in points {
+            min_x = min_x.min(x);
+            max_x = max_x.max(x);
+            min_y = min_y.min(y);
+            max_y = max_y.max(y);
+        }
+
+        let span = (max_x - min_x).max(max_y - min_y).max(1);
+        Self {
+            min_x,
+            max_y,
+            scale: (SIZE - 2.0 * MARGIN) / span as f64,
+        }
+    }
+
+    fn project(&self, x: i32, y: i32) -> (f64, f64) {
+        (
+            MARGIN + (x - self.min_x) as f64 * self.scale,
+            MARGIN + (self.max_y - y) as f64 * self.scale,
+        )
+    }
+}
+
+fn marker(out: &mut String, frame: &Frame, point: &Point, color: &str, label: &str) {
+    let (x, y) = frame.project(point.x, point.y);
+    writeln!(
+        out,
+        r#"<circle cx="{:.1}" cy="{:.1}" r="8" fill="none" stroke="{}" stroke-width="2"/>"#,
+        x, y, color
+    )
+    .unwrap();
+    writeln!(
+        out,
+        r#"<text x="{:.1}" y="{:.1}" font-family="monospace" font-size="12" fill="{}">{}</text>"#,
+        x + 10.0,
+        y - 10.0,
+        color,
+        label
+    )
+    .unwrap();
+}
+
+impl Board {
+    // Every wire in its own color, crossings as dots growing with the number
+    // of wires, the crossings closest to the central port by distance and by
+    // steps are circled.
+    pub fn to_svg(&self) -> String {
+        let frame = Frame::of(self);
+        let mut result = String::new();
+
+        writeln!(
+            result,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{s}" height="{s}" viewBox="0 0 {s} {s}">"#,
+            s = SIZE
+        )
+        .unwrap();
+        writeln!(
+            result,
+            r#"<rect width="{s}" height="{s}" fill="white"/>"#,
+            s = SIZE
+        )
+        .unwrap();
+
+        for (i, wire) in self.wires.iter().enumerate() {
+            let color = COLORS[i % COLORS.len()];
+            let points = Some((0, 0))
+                .into_iter()
+                .chain(wire.0.iter().map(|s| (s.x1, s.y1)))
+                .map(|(x, y)| {
+                    let (x, y) = frame.project(x, y);
+                    format!("{:.1},{:.1}", x, y)
+                })
+                .collect::<Vec<_>>();
+
+            writeln!(
+                result,
+                r#"<polyline fill="none" stroke="{}" stroke-width="1" points="{}"/>"#,
+                color,
+                points.join(" ")
+            )
+            .unwrap();
+            writeln!(
+                result,
+                r#"<text x="{:.1}" y="{:.1}" font-family="monospace" font-size="12" fill="{}">wire {}</text>"#,
+                MARGIN,
+                20.0 + 14.0 * i as f64,
+                color,
+                i + 1
+            )
+            .unwrap();
+        }
+
+        for crossing in self.crossing.iter() {
+            let (x, y) = frame.project(crossing.point.x, crossing.point.y);
+            let fill = if crossing.wires.len() > 2 {
+                "black"
+            } else {
+                "#555"
+            };
+            writeln!(
+                result,
+                r#"<circle cx="{:.1}" cy="{:.1}" r="{}" fill="{}"/>"#,
+                x,
+                y,
+                1 + crossing.wires.len(),
+                fill
+            )
+            .unwrap();
+        }
+
+        let (x, y) = frame.project(0, 0);
+        writeln!(
+            result,
+            r#"<rect x="{:.1}" y="{:.1}" width="8" height="8" fill="black"/>"#,
+            x - 4.0,
+            y - 4.0
+        )
+        .unwrap();
+
+        if let Some(c) = self.closest_cross() {
+            let label = format!("distance {}", c.point.distance());
+            marker(&mut result, &frame, &c.point, MANHATTAN, &label);
+        }
+
+        if let Some((c, steps)) = self.closest_steps() {
+            let label = format!("steps {}", steps);
+            marker(&mut result, &frame, &c.point, STEPS, &label);
+        }
+
+        result += "</svg>\n";
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_svg() {
+        let board = Board::parse("R8,U5,L5,D3\nU7,R6,D4,L4").unwrap();
+        let svg = board.to_svg();
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(2, svg.matches("<polyline").count());
+        assert!(svg.contains(&format!("stroke=\"{}\"", COLORS[1])));
+
+        // two crossings and the two circled ones
+        assert_eq!(2 + 2, svg.matches("<circle").count());
+        assert!(svg.contains(">distance 6</text>"));
+        assert!(svg.contains(">steps 30</text>"));
+    }
+
+    #[test]
+    fn test_frame() {
+        let board = Board::parse("R8,U5,L5,D3\nU7,R6,D4,L4").unwrap();
+        let frame = Frame::of(&board);
+
+        // 8 wide and 7 high, so the width decides the scale
+        assert_eq!((MARGIN, MARGIN + 7.0 * frame.scale), frame.project(0, 0));
+        assert_eq!((SIZE - MARGIN, MARGIN), frame.project(8, 7));
+    }
+}
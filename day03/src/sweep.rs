@@ -0,0 +1,228 @@
+use crate::{Direction, Point, Wire};
+use std::collections::{BTreeSet, HashMap};
+
+// Part of a line covered by a segment: from, to and the wire.
+type Span = (i32, i32, usize);
+
+// At the same x segments are opened before verticals are checked and closed
+// after that, so the ends of a horizontal segment are on it.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Event {
+    Open,
+    Query,
+    Close,
+}
+
+// Every point where a horizontal and a vertical segment of two different
+// wires meet, ends included, with the wire of the horizontal segment first.
+// A point at the joint of two segments is reported for both of them. Segments are swept left to right while the horizontal ones under
+// the sweep line are kept ordered by y, so it takes O((n + k) log n) for n
+// segments and k crossings.
+pub fn crossings(wires: &[Wire]) -> Vec<(Point, usize, usize)> {
+    let mut events = vec![];
+
+    for (w, wire) in wires.iter().enumerate() {
+        for (i, s) in wire.0.iter().enumerate() {
+            match s.dir {
+                Direction::Horizontal if s.x_min() < s.x_max() => {
+                    events.push((s.x_min(), Event::Open, w, i));
+                    events.push((s.x_max(), Event::Close, w, i));
+                }
+                Direction::Vertical => events.push((s.x0, Event::Query, w, i)),
+                _ => {}
+            }
+        }
+    }
+
+    events.sort_unstable();
+
+    let mut active = BTreeSet::new();
+    let mut result = vec![];
+
+    for (x, event, w, i) in events {
+        let segment = &wires[w].0[i];
+
+        match event {
+            Event::Open => {
+                active.insert((segment.y0, w, i));
+            }
+            Event::Close => {
+                active.remove(&(segment.y0, w, i));
+            }
+            Event::Query => {
+                let (low, high) = (segment.y_min(), segment.y_max() + 1);
+                for (y, other, _) in active.range((low, 0, 0)..(high, 0, 0)) {
+                    if *other != w {
+                        result.push((Point::new(x, *y), *other, w));
+                    }
+                }
+            }
+        }
+    }
+
+    result
+}
+
+// Every point shared by collinear segments of two different wires when they
+// overlap by more than a single point. Segments on the same line are sorted
+// by where they start and compared only with the ones still reaching there.
+pub fn overlaps(wires: &[Wire]) -> Vec<(Point, usize, usize)> {
+    let mut lines: HashMap<(bool, i32), Vec<Span>> = HashMap::new();
+
+    for (w, wire) in wires.iter().enumerate() {
+        for s in wire.0.iter() {
+            let (key, low, high) = match s.dir {
+                Direction::Horizontal => ((false, s.y0), s.x_min(), s.x_max()),
+                Direction::Vertical => ((true, s.x0), s.y_min(), s.y_max()),
+            };
+
+            if low < high {
+                lines.entry(key).or_default().push((low, high, w));
+            }
+        }
+    }
+
+    let mut result = vec![];
+
+    for ((vertical, at), mut spans) in lines {
+        spans.sort_unstable();
+
+        let mut active: Vec<(i32, usize)> = vec![];
+        for (low, high, w) in spans {
+            active.retain(|(end, _)| *end > low);
+
+            for (end, other) in active.iter().filter(|(_, other)| *other != w) {
+                for i in low..=high.min(*end) {
+                    let point = if vertical {
+                        Point::new(at, i)
+                    } else {
+                        Point::new(i, at)
+                    };
+                    result.push((point, *other, w));
+                }
+            }
+
+            active.push((high, w));
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{cross, Board};
+
+    type Found = (i32, i32, usize, usize);
+
+    // Crossings and overlaps found by the sweep and by checking every pair of
+    // segments, both sorted.
+    fn both(wires: &[Wire]) -> (Vec<Found>, Vec<Found>) {
+        let mut expected = vec![];
+        for (a, wire_a) in wires.iter().enumerate() {
+            for (b, wire_b) in wires.iter().enumerate().skip(a + 1) {
+                for sa in wire_a.0.iter() {
+                    for sb in wire_b.0.iter() {
+                        for p in cross(sa, sb) {
+                            expected.push((p.x, p.y, a, b));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut result = crossings(wires)
+            .into_iter()
+            .chain(overlaps(wires))
+            .map(|(p, a, b)| (p.x, p.y, a.min(b), a.max(b)))
+            .collect::<Vec<_>>();
+
+        expected.sort_unstable();
+        result.sort_unstable();
+        (expected, result)
+    }
+
+    #[test]
+    fn test_same_as_pairwise() {
+        let wires = vec![
+            Wire::random(300, 20, 1),
+            Wire::random(300, 20, 2),
+            Wire::random(300, 20, 3),
+        ];
+        let (expected, result) = both(&wires);
+
+        assert!(!expected.is_empty());
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_split_steps() {
+        // the crossing at (5,0) lies where the second wire's steps meet
+        for input in ["R10\nU3,R5,D3,R0,D3", "R10\nU3,R5,D3,D3"].iter() {
+            let board = Board::parse(input).unwrap();
+            let (expected, result) = both(&board.wires);
+
+            // the second wire also starts on the first one
+            assert_eq!(vec![(0, 0, 0, 1), (5, 0, 0, 1)], expected);
+            assert_eq!(expected, result);
+            assert_eq!(Some(5), board.cross_distance());
+        }
+    }
+
+    #[test]
+    fn test_touching() {
+        let wires = vec![
+            Wire::parse("R10").unwrap(),
+            Wire::parse("U5,R10,D10").unwrap(),
+            Wire::parse("D1,R5,U2").unwrap(),
+        ];
+
+        // the sweep reports where segments meet, including the central port
+        // and the end of the first wire the second one touches
+        let mut result = crossings(&wires);
+        result.sort_unstable();
+        assert_eq!(
+            vec![
+                (Point::new(0, 0), 0, 1),
+                (Point::new(0, 0), 0, 2),
+                (Point::new(5, 0), 0, 2),
+                (Point::new(10, 0), 0, 1),
+            ],
+            result
+        );
+        assert_eq!(Vec::<(Point, usize, usize)>::new(), overlaps(&wires));
+
+        // only the third wire crosses the first one
+        let board = Board::new(wires);
+        assert_eq!(
+            vec![(0, 2, Point::new(5, 0))],
+            board.pairs().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let wires = vec![
+            Wire::parse("U2,R10").unwrap(),
+            Wire::parse("U1,R4,U1,R2,U1").unwrap(),
+            Wire::parse("U2,R0,R3").unwrap(),
+        ];
+
+        let mut result = overlaps(&wires)
+            .into_iter()
+            .map(|(p, a, b)| (a.min(b), a.max(b), p.x, p.y))
+            .collect::<Vec<_>>();
+        result.sort_unstable();
+
+        // all three go up together, the first one then runs along the other
+        // two, the zero-length step of the third one changes nothing
+        let mut expected = vec![(0, 1, 0, 0), (0, 1, 0, 1), (1, 2, 0, 0), (1, 2, 0, 1)];
+        expected.extend((0..=2).map(|y| (0, 2, 0, y)));
+        expected.extend((4..=6).map(|x| (0, 1, x, 2)));
+        expected.extend((0..=3).map(|x| (0, 2, x, 2)));
+        expected.sort_unstable();
+
+        assert_eq!(expected, result);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::rules::Rule;
+use crate::{Num, Puzzle, Result};
+use std::collections::HashMap;
+
+// What is left to know about a prefix: its last digit, the length of the
+// run it ends with and one flag for every atomic rule.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+struct State {
+    last: Option<u8>,
+    run: usize,
+    flags: Vec<bool>,
+}
+
+// Atomic rules of `rule` in the order `eval` reads them.
+fn atoms<'a>(rule: &'a Rule, result: &mut Vec<&'a Rule>) {
+    match rule {
+        Rule::Not(rule) => atoms(rule, result),
+        Rule::All(rules) | Rule::Any(rules) => rules.iter().for_each(|r| atoms(r, result)),
+        atom => result.push(atom),
+    }
+}
+
+// Every child is evaluated, so each one reads its own atoms.
+fn eval(rule: &Rule, values: &mut impl Iterator<Item = bool>) -> bool {
+    let mut children = |rules: &[Rule]| rules.iter().map(|r| eval(r, values)).collect::<Vec<_>>();
+
+    match rule {
+        Rule::Not(rule) => !eval(rule, values),
+        Rule::All(rules) => children(rules).into_iter().all(|v| v),
+        Rule::Any(rules) => children(rules).into_iter().any(|v| v),
+        _ => values.next().unwrap_or(false),
+    }
+}
+
+struct Counter<'a> {
+    rule: &'a Rule,
+    atoms: Vec<&'a Rule>,
+    base: u8,
+    // longer runs are all the same to every rule
+    cap: usize,
+    // prefixes going down can be dropped right away
+    monotone: bool,
+}
+
+impl<'a> Counter<'a> {
+    fn new(rule: &'a Rule, base: u8) -> Self {
+        let mut result = vec![];
+        atoms(rule, &mut result);
+
+        let cap = result
+            .iter()
+            .map(|atom| match atom {
+                Rule::RunExactly(k) | Rule::RunAtLeast(k) => k + 1,
+                _ => 1,
+            })
+            .max()
+            .unwrap_or(1);
+
+        Self {
+            rule,
+            atoms: result,
+            base,
+            cap,
+            monotone: rule.is_non_decreasing(),
+        }
+    }
+
+    fn start(&self) -> State {
+        State {
+            last: None,
+            run: 0,
+            flags: vec![false; self.atoms.len()],
+        }
+    }
+
+    fn push(&self, state: &State, digit: u8) -> Option<State> {
+        if self.monotone && state.last.is_some_and(|last| digit < last) {
+            return None;
+        }
+
+        let same = state.last == Some(digit);
+        let run = if same {
+            (state.run + 1).min(self.cap)
+        } else {
+            1
+        };
+
+        let flags = self
+            .atoms
+            .iter()
+            .zip(state.flags.iter())
+            .map(|(atom, flag)| match atom {
+                Rule::NonDecreasing => *flag || state.last.is_some_and(|last| digit < last),
+                Rule::RunExactly(k) => *flag || (!same && state.run == *k),
+                Rule::RunAtLeast(k) => *flag || run >= *k,
+                Rule::Forbidden(digits) => *flag || digits.contains(&digit),
+                _ => *flag,
+            })
+            .collect();
+
+        Some(State {
+            last: Some(digit),
+            run,
+            flags,
+        })
+    }
+
+    fn accepts(&self, state: &State) -> bool {
+        let mut values = self
+            .atoms
+            .iter()
+            .zip(state.flags.iter())
+            .map(|(atom, flag)| match atom {
+                Rule::NonDecreasing | Rule::Forbidden(_) => !flag,
+                Rule::RunExactly(k) => *flag || state.run == *k,
+                _ => *flag,
+            });
+
+        eval(self.rule, &mut values)
+    }
+
+    // Numbers of the same length as `bound` that are below it, or not above
+    // it when `inclusive`, and pass the rule.
+    fn count_below(&self, bound: &Num, inclusive: bool) -> Result<u128> {
+        // numbers already below the bound and the bound's own prefix
+        let mut free: HashMap<State, u128> = HashMap::new();
+        let mut tight = Some(self.start());
+
+        for digit in bound.0.iter() {
+            let mut next: HashMap<State, u128> = HashMap::new();
+
+            for (state, count) in free.iter() {
+                for d in 0..self.base {
+                    if let Some(state) = self.push(state, d) {
+                        let entry = next.entry(state).or_insert(0);
+                        *entry = entry.checked_add(*count).ok_or("count overflow")?;
+                    }
+                }
+            }
+
+            if let Some(prefix) = tight {
+                for d in 0..*digit {
+                    if let Some(state) = self.push(&prefix, d) {
+                        *next.entry(state).or_insert(0) += 1;
+                    }
+                }
+                tight = self.push(&prefix, *digit);
+            }
+
+            free = next;
+        }
+
+        let mut result = 0_u128;
+        for (_, count) in free.iter().filter(|(state, _)| self.accepts(state)) {
+            result = result.checked_add(*count).ok_or("count overflow")?;
+        }
+
+        if inclusive && tight.is_some_and(|state| self.accepts(&state)) {
+            result += 1;
+        }
+
+        Ok(result)
+    }
+}
+
+impl Puzzle {
+    // Same as `count`, but without looking at every number: numbers sharing
+    // what the rule needs to know about them are counted together digit by
+    // digit.
+    pub fn count_dp(&self, rule: &Rule) -> Result<u128> {
+        let counter = Counter::new(rule, self.base);
+        if self.from > self.to {
+            return Ok(0);
+        }
+
+        let to = counter.count_below(&self.to, true)?;
+        let from = counter.count_below(&self.from, false)?;
+        Ok(to - from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check(input: &str) {
+        let puzzle = Puzzle::parse(input).unwrap();
+        for rule in puzzle.parts.iter() {
+            assert_eq!(
+                puzzle.count(rule) as u128,
+                puzzle.count_dp(rule).unwrap(),
+                "{} / {}",
+                input.lines().next().unwrap(),
+                rule
+            );
+        }
+    }
+
+    #[test]
+    fn test_puzzle() {
+        let puzzle = Puzzle::parse("137683-596253").unwrap();
+        assert_eq!(1864, puzzle.count_dp(&puzzle.parts[0]).unwrap());
+        assert_eq!(1258, puzzle.count_dp(&puzzle.parts[1]).unwrap());
+    }
+
+    #[test]
+    fn test_same_as_enumeration() {
+        let parts = "part non-decreasing & run>=2\n\
+                     part non-decreasing & run=2\n\
+                     part run=3 | run>=5\n\
+                     part !non-decreasing & forbid=09\n\
+                     part !run>=2 | run=1 & !forbid=7";
+
+        for range in [
+            "000000-999999",
+            "137683-596253",
+            "111111-111111",
+            "5-420000",
+        ]
+        .iter()
+        {
+            check(&format!("{}\n{}", range, parts));
+        }
+
+        check("10-2211\nbase 3\nlength 7\npart run=2 & non-decreasing\npart run>=3");
+    }
+
+    #[test]
+    fn test_long() {
+        let from = "1".repeat(40);
+        let to = "9".repeat(40);
+        let puzzle = Puzzle::parse(&format!("{}-{}", from, to)).unwrap();
+
+        // every non-decreasing 40 digit number without a zero, 40 digits out
+        // of 9 always repeat one
+        let all = (1..=8).fold(1_u128, |a, i| a * (40 + i) / i);
+        assert_eq!(all, puzzle.count_dp(&puzzle.parts[0]).unwrap());
+
+        let rule = Rule::parse("run>=2").unwrap();
+        assert!(puzzle.count_dp(&rule).is_err());
+    }
+}
@@ -1,86 +1,234 @@
+use rules::Rule;
+use std::env;
+use std::error::Error;
+use std::fs;
 use std::time::Instant;
 
-#[derive(Debug, PartialEq, PartialOrd)]
-struct Num([u8; 6]);
+pub type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+mod dp;
+mod rules;
+
+// Digits of a fixed length number, most significant first.
+#[derive(Debug, PartialEq, PartialOrd, Clone)]
+struct Num(Vec<u8>);
 
 impl Num {
-    fn from_i32(input: i32) -> Self {
-        let d0 = ((input / 100_000) % 10) as u8;
-        let d1 = ((input / 10_000) % 10) as u8;
-        let d2 = ((input / 1_000) % 10) as u8;
-        let d3 = ((input / 100) % 10) as u8;
-        let d4 = ((input / 10) % 10) as u8;
-        let d5 = (input % 10) as u8;
-
-        Self {
-            0: [d0, d1, d2, d3, d4, d5],
+    // Shorter numbers are padded with zeros up to `len` digits.
+    fn parse(input: &str, len: usize, base: u8) -> Result<Self> {
+        let digits = input
+            .chars()
+            .map(|c| c.to_digit(base as u32).map(|d| d as u8))
+            .collect::<Option<Vec<_>>>()
+            .filter(|d| !d.is_empty())
+            .ok_or_else(|| format!("{:?} is not a base {} number", input, base))?;
+
+        if digits.len() > len {
+            return Err(format!("{} has more than {} digits", input, len).into());
         }
+
+        let mut result = vec![0; len - digits.len()];
+        result.extend(digits);
+        Ok(Self(result))
     }
 
-    fn inc(&mut self) {
-        for i in (0..6).into_iter().rev() {
-            self.0[i] += 1;
-            if self.0[i] != 10 {
-                return;
+    // Returns false on overflow.
+    fn inc(&mut self, base: u8) -> bool {
+        for digit in self.0.iter_mut().rev() {
+            *digit += 1;
+            if *digit != base {
+                return true;
             }
+            *digit = 0;
+        }
 
-            if i == 0 {
-                self.0[i] = 0;
-            } else {
-                self.0[i] = self.0[i - 1];
-            }
+        false
+    }
+
+    // Smallest non-decreasing number not less than this one.
+    fn skip_decreasing(&mut self) {
+        if let Some(i) = (1..self.0.len()).find(|i| self.0[*i] < self.0[i - 1]) {
+            let digit = self.0[i - 1];
+            self.0[i..].iter_mut().for_each(|d| *d = digit);
         }
     }
 }
 
-fn is_2_digit_same(input: &Num) -> bool {
-    (0..5).any(|i| input.0[i] == input.0[i + 1])
+// Puzzle variant: the range from the first line and optional settings.
+//
+//   137683-596253
+//   base 10
+//   length 6
+//   part non-decreasing & run>=2
+#[derive(Debug)]
+struct Puzzle {
+    base: u8,
+    from: Num,
+    to: Num,
+    parts: Vec<Rule>,
 }
 
-fn is_2_digit_same_advanced(input: &Num) -> bool {
-    let input = input.0;
-
-    (0..5).any(|i| match i {
-        0 => (input[0] == input[1]) && (input[0] != input[2]),
-        4 => (input[4] == input[5]) && (input[4] != input[3]),
-        n => (input[n] == input[n + 1]) && (input[n] != input[n - 1]) && (input[n] != input[n + 2]),
-    })
-}
+impl Puzzle {
+    fn parse(input: &str) -> Result<Self> {
+        let mut lines = input.lines().map(str::trim).filter(|l| !l.is_empty());
+        let range = lines.next().ok_or("missing range")?;
+        let (from, to) = range
+            .split_once('-')
+            .ok_or_else(|| format!("invalid range {:?}", range))?;
+
+        let mut base = 10;
+        let mut len = None;
+        let mut parts = vec![];
+
+        for line in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "base" => base = value.parse()?,
+                "length" => len = Some(value.parse()?),
+                "part" => parts.push(Rule::parse(value)?),
+                _ => return Err(format!("unknown setting {:?}", line).into()),
+            }
+        }
 
-fn is_increase(input: &Num) -> bool {
-    (0..5).all(|i| input.0[i] <= input.0[i + 1])
-}
+        if !(2..=36).contains(&base) {
+            return Err(format!("base {} is not supported", base).into());
+        }
 
-fn main() {
-    let now = Instant::now();
+        if parts.is_empty() {
+            parts.push(Rule::parse("non-decreasing & run>=2")?);
+            parts.push(Rule::parse("non-decreasing & run=2")?);
+        }
 
-    let mut c = Num::from_i32(137683);
-    let max = Num::from_i32(596253);
+        let len = len.unwrap_or(to.len());
+        Ok(Self {
+            base,
+            from: Num::parse(from, len, base)?,
+            to: Num::parse(to, len, base)?,
+            parts,
+        })
+    }
 
-    let mut task_a = 0;
-    let mut task_b = 0;
+    // Walks every candidate in the range, only the non-decreasing ones when
+    // the rule allows nothing else.
+    fn count(&self, rule: &Rule) -> usize {
+        let monotone = rule.is_non_decreasing();
+        let mut num = self.from.clone();
+        let mut result = 0;
 
-    loop {
-        if is_increase(&c) {
-            if is_2_digit_same(&c) {
-                task_a += 1;
+        loop {
+            if monotone {
+                num.skip_decreasing();
             }
 
-            if is_2_digit_same_advanced(&c) {
-                task_b += 1;
+            if num > self.to {
+                break;
             }
-        }
 
-        c.inc();
+            if rule.check(&num.0) {
+                result += 1;
+            }
 
-        if c > max {
-            break;
+            if !num.inc(self.base) {
+                break;
+            }
         }
+
+        result
     }
+}
+
+fn main() -> Result<()> {
+    let now = Instant::now();
+
+    let input = fs::read_to_string("input.txt")?;
+    let puzzle = Puzzle::parse(&input)?;
+
+    let counts = puzzle
+        .parts
+        .iter()
+        .map(|rule| Ok((rule, puzzle.count_dp(rule)?)))
+        .collect::<Result<Vec<_>>>()?;
 
     let total_time = now.elapsed();
 
-    println!("Part I:  {}", task_a);
-    println!("Part II: {}", task_b);
+    for (i, (rule, count)) in counts.iter().enumerate() {
+        println!("Part {}: {} ({})", i + 1, count, rule);
+    }
     println!("Total time: {}μs", total_time.as_micros());
+
+    // day04 enumerate - checks the counts by walking the whole range
+    if env::args().nth(1).as_deref() == Some("enumerate") {
+        let now = Instant::now();
+        for (i, (rule, count)) in counts.iter().enumerate() {
+            let expected = puzzle.count(rule);
+            let status = if expected as u128 == *count {
+                "ok"
+            } else {
+                "MISMATCH"
+            };
+            println!("Part {}: {} enumerated, {}", i + 1, expected, status);
+        }
+        println!("Enumeration time: {}μs", now.elapsed().as_micros());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_num() {
+        assert_eq!(Num(vec![0, 0, 4, 2]), Num::parse("42", 4, 10).unwrap());
+        assert_eq!(Num(vec![1, 0, 1]), Num::parse("101", 3, 2).unwrap());
+        assert_eq!(Num(vec![15, 0]), Num::parse("f0", 2, 16).unwrap());
+        assert!(Num::parse("12345", 4, 10).is_err());
+        assert!(Num::parse("102", 3, 2).is_err());
+
+        let mut num = Num(vec![0, 1, 1]);
+        assert!(num.inc(2));
+        assert_eq!(Num(vec![1, 0, 0]), num);
+        let mut num = Num(vec![1, 1]);
+        assert!(!num.inc(2));
+
+        let mut num = Num(vec![1, 3, 2, 9]);
+        num.skip_decreasing();
+        assert_eq!(Num(vec![1, 3, 3, 3]), num);
+    }
+
+    #[test]
+    fn test_puzzle() {
+        let puzzle = Puzzle::parse("137683-596253\n").unwrap();
+        assert_eq!(1864, puzzle.count(&puzzle.parts[0]));
+        assert_eq!(1258, puzzle.count(&puzzle.parts[1]));
+    }
+
+    #[test]
+    fn test_variant() {
+        let input = "0-777\nbase 8\nlength 4\npart run=3 & forbid=0\npart non-decreasing";
+        let puzzle = Puzzle::parse(input).unwrap();
+        assert_eq!(4, puzzle.from.0.len());
+
+        // brute force over the same range
+        let digits = |n: u32| {
+            (0..4)
+                .rev()
+                .map(|i| (n / 8u32.pow(i) % 8) as u8)
+                .collect::<Vec<_>>()
+        };
+        for rule in puzzle.parts.iter() {
+            let expected = (0..=0o777).filter(|n| rule.check(&digits(*n))).count();
+            assert_eq!(expected, puzzle.count(rule));
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Puzzle::parse("").is_err());
+        assert!(Puzzle::parse("100").is_err());
+        assert!(Puzzle::parse("1-9\nbase 40").is_err());
+        assert!(Puzzle::parse("1-9\ncolor red").is_err());
+        assert!(Puzzle::parse("1-9\npart run=").is_err());
+    }
 }
@@ -0,0 +1,201 @@
+use std::fmt;
+
+// Predicate over the digits of a password, most significant digit first.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Rule {
+    NonDecreasing,
+    RunExactly(usize),
+    RunAtLeast(usize),
+    Forbidden(Vec<u8>),
+    Not(Box<Rule>),
+    All(Vec<Rule>),
+    Any(Vec<Rule>),
+}
+
+// Lengths of the groups of equal adjacent digits.
+fn runs(digits: &[u8]) -> Vec<usize> {
+    let mut result: Vec<usize> = vec![];
+    for (i, digit) in digits.iter().enumerate() {
+        match result.last_mut() {
+            Some(len) if digits[i - 1] == *digit => *len += 1,
+            _ => result.push(1),
+        }
+    }
+
+    result
+}
+
+impl Rule {
+    pub fn check(&self, digits: &[u8]) -> bool {
+        match self {
+            Self::NonDecreasing => digits.windows(2).all(|w| w[0] <= w[1]),
+            Self::RunExactly(k) => runs(digits).contains(k),
+            Self::RunAtLeast(k) => runs(digits).iter().any(|len| len >= k),
+            Self::Forbidden(forbidden) => !digits.iter().any(|d| forbidden.contains(d)),
+            Self::Not(rule) => !rule.check(digits),
+            Self::All(rules) => rules.iter().all(|r| r.check(digits)),
+            Self::Any(rules) => rules.iter().any(|r| r.check(digits)),
+        }
+    }
+
+    // Whether only non-decreasing sequences can pass, which lets the
+    // enumeration skip everything else.
+    pub fn is_non_decreasing(&self) -> bool {
+        match self {
+            Self::NonDecreasing => true,
+            Self::All(rules) => rules.iter().any(|r| r.is_non_decreasing()),
+            Self::Any(rules) => !rules.is_empty() && rules.iter().all(|r| r.is_non_decreasing()),
+            _ => false,
+        }
+    }
+
+    // `|` binds weaker than `&`, `!` negates a single predicate:
+    //
+    //   non-decreasing & run>=2 & !forbid=05 | run=6
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let any = input
+            .split('|')
+            .map(|part| {
+                let all = part
+                    .split('&')
+                    .map(|atom| Self::parse_atom(atom.trim()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Self::all(all))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(if any.len() == 1 {
+            any.into_iter().next().unwrap()
+        } else {
+            Self::Any(any)
+        })
+    }
+
+    fn all(mut rules: Vec<Rule>) -> Self {
+        if rules.len() == 1 {
+            rules.pop().unwrap()
+        } else {
+            Self::All(rules)
+        }
+    }
+
+    fn parse_atom(input: &str) -> Result<Self, String> {
+        if let Some(rest) = input.strip_prefix('!') {
+            return Ok(Self::Not(Box::new(Self::parse_atom(rest.trim())?)));
+        }
+
+        let count = |value: &str| {
+            value
+                .parse::<usize>()
+                .ok()
+                .filter(|k| *k > 0)
+                .ok_or_else(|| format!("invalid run length {:?}", value))
+        };
+
+        if input == "non-decreasing" {
+            Ok(Self::NonDecreasing)
+        } else if let Some(value) = input.strip_prefix("run>=") {
+            Ok(Self::RunAtLeast(count(value)?))
+        } else if let Some(value) = input.strip_prefix("run=") {
+            Ok(Self::RunExactly(count(value)?))
+        } else if let Some(value) = input.strip_prefix("forbid=") {
+            let digits = value
+                .chars()
+                .map(|c| c.to_digit(36).map(|d| d as u8))
+                .collect::<Option<Vec<_>>>()
+                .filter(|d| !d.is_empty())
+                .ok_or_else(|| format!("invalid digits {:?}", value))?;
+            Ok(Self::Forbidden(digits))
+        } else {
+            Err(format!("unknown rule {:?}", input))
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |rules: &[Rule], sep: &str| {
+            rules
+                .iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<_>>()
+                .join(sep)
+        };
+
+        match self {
+            Self::NonDecreasing => write!(f, "non-decreasing"),
+            Self::RunExactly(k) => write!(f, "run={}", k),
+            Self::RunAtLeast(k) => write!(f, "run>={}", k),
+            Self::Forbidden(digits) => {
+                let digits = digits
+                    .iter()
+                    .filter_map(|d| std::char::from_digit(*d as u32, 36))
+                    .collect::<String>();
+                write!(f, "forbid={}", digits)
+            }
+            Self::Not(rule) => write!(f, "!{}", rule),
+            Self::All(rules) => write!(f, "{}", join(rules, " & ")),
+            Self::Any(rules) => write!(f, "{}", join(rules, " | ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn digits(input: &str) -> Vec<u8> {
+        input.bytes().map(|b| b - b'0').collect()
+    }
+
+    #[test]
+    fn test_puzzle_rules() {
+        let part_a = Rule::parse("non-decreasing & run>=2").unwrap();
+        assert!(part_a.check(&digits("111111")));
+        assert!(!part_a.check(&digits("223450")));
+        assert!(!part_a.check(&digits("123789")));
+
+        let part_b = Rule::parse("non-decreasing & run=2").unwrap();
+        assert!(part_b.check(&digits("112233")));
+        assert!(!part_b.check(&digits("123444")));
+        assert!(part_b.check(&digits("111122")));
+    }
+
+    #[test]
+    fn test_composition() {
+        let rule = Rule::parse("non-decreasing & !forbid=05 | run=6").unwrap();
+        assert_eq!(
+            Rule::Any(vec![
+                Rule::All(vec![
+                    Rule::NonDecreasing,
+                    Rule::Not(Box::new(Rule::Forbidden(vec![0, 5])))
+                ]),
+                Rule::RunExactly(6),
+            ]),
+            rule
+        );
+
+        // not forbidding 0 and 5 means one of them is required
+        assert!(rule.check(&digits("123456")));
+        assert!(!rule.check(&digits("123467")));
+        assert!(rule.check(&digits("999999")));
+        assert!(!rule.is_non_decreasing());
+
+        assert_eq!("non-decreasing & !forbid=05 | run=6", rule.to_string());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Rule::parse("run>=0").is_err());
+        assert!(Rule::parse("run=x").is_err());
+        assert!(Rule::parse("forbid=").is_err());
+        assert!(Rule::parse("increasing").is_err());
+        assert!(Rule::parse("non-decreasing &").is_err());
+    }
+
+    #[test]
+    fn test_runs() {
+        assert_eq!(vec![2, 1, 3], runs(&digits("112333")));
+        assert_eq!(Vec::<usize>::new(), runs(&[]));
+    }
+}
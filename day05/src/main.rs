@@ -1,6 +1,10 @@
 use std::fs;
 use std::time::Instant;
 
+#[cfg(test)]
+#[path = "../../intcode/conformance.rs"]
+mod conformance;
+
 #[derive(Debug, PartialEq)]
 enum Mode {
     Position,
@@ -23,7 +27,7 @@ type ModeSet = (Mode, Mode, Mode);
 enum Command {
     Halt,
     Input(usize),
-    Output(usize),
+    Output(i32, ModeSet),
     Add(i32, i32, i32, ModeSet),
     Mul(i32, i32, i32, ModeSet),
     JumpTrue(i32, i32, ModeSet),
@@ -113,8 +117,8 @@ impl CPU {
                 let value = self.input.remove(0);
                 self.mem[*addr] = value;
             }
-            Command::Output(addr) => {
-                let value = self.mem[*addr];
+            Command::Output(a, modeset) => {
+                let value = self.get_value(a, &modeset.0);
                 self.output.push(value);
             }
             Command::Add(a, b, c, modeset) => {
@@ -194,7 +198,7 @@ fn decode(mem: &[i32]) -> Command {
         1 => Command::Add(mem[1], mem[2], mem[3], modeset),
         2 => Command::Mul(mem[1], mem[2], mem[3], modeset),
         3 => Command::Input(mem[1] as usize),
-        4 => Command::Output(mem[1] as usize),
+        4 => Command::Output(mem[1], modeset),
         5 => Command::JumpTrue(mem[1], mem[2], modeset),
         6 => Command::JumpFalse(mem[1], mem[2], modeset),
         7 => Command::LessThan(mem[1], mem[2], mem[3], modeset),
@@ -291,4 +295,17 @@ mod test {
 
         assert_eq!(cpu.mem[1], 3);
     }
+
+    // Values are narrowed to i32, there is nothing past day 5 to run.
+    #[test]
+    fn test_conformance() {
+        let narrow = |values: &[i64]| values.iter().map(|v| *v as i32).collect::<Vec<_>>();
+
+        crate::conformance::check(&["day02", "day05"], |programm, input| {
+            let mut cpu = CPU::new(narrow(programm), narrow(input));
+            cpu.run();
+            let widen = |values: &[i32]| values.iter().map(|v| *v as i64).collect();
+            (widen(&cpu.output), widen(&cpu.mem))
+        });
+    }
 }
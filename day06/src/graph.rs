@@ -0,0 +1,206 @@
+use crate::tree::OrbitTree;
+use crate::OrbitInfo;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fmt::Write;
+
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    Malformed(usize, String),
+    Parents(String, Vec<String>),
+    Cycle(Vec<String>),
+    Disconnected(String, usize),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use Problem::*;
+
+        match self {
+            Malformed(line, error) => write!(fmt, "line {}: {}", line, error),
+            Parents(name, parents) => write!(fmt, "{} orbits {}", name, parents.join(", ")),
+            Cycle(path) => write!(fmt, "cycle {}", path.join(" -> ")),
+            Disconnected(name, 0) => write!(fmt, "{} is cut off from the root", name),
+            Disconnected(name, n) => write!(
+                fmt,
+                "{} and {} objects orbiting it are cut off from the root",
+                name, n
+            ),
+        }
+    }
+}
+
+// Every object must orbit exactly one other object and lead to `root`.
+// Objects with several parents are followed through the first one.
+pub fn validate(input: &str, root: &str) -> Vec<Problem> {
+    let mut result = vec![];
+    let mut parents: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut names = BTreeSet::new();
+
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match OrbitInfo::parse(line.trim()) {
+            Ok(info) => {
+                names.insert(info.primary.clone());
+                names.insert(info.secondary.clone());
+                parents
+                    .entry(info.secondary)
+                    .or_default()
+                    .push(info.primary);
+            }
+            Err(e) => result.push(Problem::Malformed(i + 1, e.to_string())),
+        }
+    }
+
+    for (name, list) in parents.iter().filter(|(_, list)| list.len() > 1) {
+        result.push(Problem::Parents(name.clone(), list.clone()));
+    }
+
+    // Top of every object: the root of its tree, None for objects on a cycle.
+    // Objects hanging off a cycle get the one right below the cycle as their
+    // top. Walked without recursion, chains can be long.
+    let mut top: HashMap<&str, Option<&str>> = HashMap::new();
+    for name in names.iter() {
+        let mut path = vec![];
+        let mut on_path = HashMap::new();
+        let mut object = name.as_str();
+
+        let end = loop {
+            if let Some(end) = top.get(object) {
+                break end.or_else(|| path.last().copied());
+            }
+
+            if let Some(start) = on_path.get(object).copied() {
+                let mut cycle = path[start..]
+                    .iter()
+                    .map(|n: &&str| n.to_string())
+                    .collect::<Vec<_>>();
+                cycle.push(object.to_owned());
+                result.push(Problem::Cycle(cycle));
+
+                for n in path.drain(start..) {
+                    top.insert(n, None);
+                }
+                break path.last().copied();
+            }
+
+            on_path.insert(object, path.len());
+            path.push(object);
+            match parents.get(object) {
+                Some(list) => object = &list[0],
+                None => break Some(object),
+            }
+        };
+
+        path.into_iter().for_each(|n| {
+            top.insert(n, end);
+        });
+    }
+
+    let mut components = BTreeMap::new();
+    for end in top.values().flatten().filter(|end| **end != root) {
+        *components.entry(*end).or_insert(0) += 1;
+    }
+
+    for (name, count) in components {
+        result.push(Problem::Disconnected(name.to_owned(), count - 1));
+    }
+
+    result
+}
+
+impl OrbitTree {
+    // Graphviz graph, edges go from every object to its satellites.
+    pub fn to_dot(&self) -> String {
+        let mut result = String::new();
+        writeln!(result, "digraph orbits {{").unwrap();
+
+        for name in self.objects() {
+            match self.parent(name).unwrap() {
+                Some(parent) => writeln!(result, "    \"{}\" -> \"{}\";", parent, name).unwrap(),
+                None => writeln!(result, "    \"{}\" [shape=box];", name).unwrap(),
+            }
+        }
+
+        writeln!(result, "}}").unwrap();
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_valid() {
+        let input = "COM)B\nB)C\nC)D\nD)E\nE)F\nB)G\nG)H\nD)I\nE)J\nJ)K\nK)L\n";
+        assert_eq!(Vec::<Problem>::new(), validate(input, "COM"));
+    }
+
+    #[test]
+    fn test_problems() {
+        let input = "COM)A\nA)B\nC)B\n\nA-D\nX)Y\nY)Z\nP)Q\nQ)P\nQ)R\nCOM)COM2)E\n";
+        let problems = validate(input, "COM");
+
+        assert_eq!(
+            vec![
+                Problem::Malformed(5, "expected PRIMARY)SECONDARY, got \"A-D\"".to_owned()),
+                Problem::Malformed(
+                    11,
+                    "expected PRIMARY)SECONDARY, got \"COM)COM2)E\"".to_owned()
+                ),
+                Problem::Parents("B".to_owned(), vec!["A".to_owned(), "C".to_owned()]),
+                Problem::Cycle(vec!["P".to_owned(), "Q".to_owned(), "P".to_owned()]),
+                Problem::Disconnected("C".to_owned(), 0),
+                Problem::Disconnected("R".to_owned(), 0),
+                Problem::Disconnected("X".to_owned(), 2),
+            ],
+            problems
+        );
+
+        assert_eq!("B orbits A, C", problems[2].to_string());
+        assert_eq!("cycle P -> Q -> P", problems[3].to_string());
+        assert_eq!("C is cut off from the root", problems[4].to_string());
+        assert_eq!("R is cut off from the root", problems[5].to_string());
+        assert_eq!(
+            "X and 2 objects orbiting it are cut off from the root",
+            problems[6].to_string()
+        );
+    }
+
+    #[test]
+    fn test_off_cycle() {
+        // S, T and U hang off the cycle below S, V right below P
+        let input = "COM)A\nP)Q\nQ)P\nQ)S\nS)T\nT)U\nP)V\n";
+        let problems = validate(input, "COM");
+
+        assert_eq!(
+            vec![
+                Problem::Cycle(vec!["P".to_owned(), "Q".to_owned(), "P".to_owned()]),
+                Problem::Disconnected("S".to_owned(), 2),
+                Problem::Disconnected("V".to_owned(), 0),
+            ],
+            problems
+        );
+    }
+
+    #[test]
+    fn test_missing_root() {
+        let problems = validate("A)B\nB)C", "COM");
+        assert_eq!(vec![Problem::Disconnected("A".to_owned(), 2)], problems);
+    }
+
+    #[test]
+    fn test_dot() {
+        let list = OrbitInfo::parse_all("COM)B\nB)C\nB)D").unwrap();
+        let dot = OrbitTree::build(&list).unwrap().to_dot();
+
+        assert_eq!(
+            "digraph orbits {\n    \"COM\" [shape=box];\n    \"COM\" -> \"B\";\n    \"B\" -> \"C\";\n    \"B\" -> \"D\";\n}\n",
+            dot
+        );
+    }
+}
@@ -1,8 +1,12 @@
-use std::collections::HashMap;
+use std::env;
 use std::fs;
+use tree::OrbitTree;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
+mod graph;
+mod tree;
+
 #[derive(Debug, PartialEq)]
 struct OrbitInfo {
     primary: String,
@@ -11,11 +15,27 @@ struct OrbitInfo {
 
 impl OrbitInfo {
     pub fn parse(input: &str) -> Result<OrbitInfo> {
-        let mut parts = input.trim_matches('\n').split(')');
-        let primary = parts.next().ok_or("invalid input")?;
-        let secondary = parts.next().ok_or("invalid input")?;
+        let input = input.trim_matches('\n');
+        let valid = |name: &str| !name.is_empty() && name.chars().all(|c| c.is_alphanumeric());
+
+        match input.split(')').collect::<Vec<_>>().as_slice() {
+            [primary, secondary] if valid(primary) && valid(secondary) => {
+                Ok(OrbitInfo::new(primary, secondary))
+            }
+            _ => Err(format!("expected PRIMARY)SECONDARY, got {:?}", input).into()),
+        }
+    }
 
-        Ok(OrbitInfo::new(primary, secondary))
+    // Empty lines are skipped, errors point at the line.
+    pub fn parse_all(input: &str) -> Result<Vec<OrbitInfo>> {
+        input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                OrbitInfo::parse(line.trim()).map_err(|e| format!("line {}: {}", i + 1, e).into())
+            })
+            .collect()
     }
 
     pub fn new(primary: &str, secondary: &str) -> Self {
@@ -26,103 +46,59 @@ impl OrbitInfo {
     }
 }
 
-#[derive(Debug)]
-struct System {
-    orbits: HashMap<String, String>,
-    distance: HashMap<String, i32>,
-}
+// day06 query <a> <b>
+fn query(tree: &OrbitTree, a: &str, b: &str) -> Result<()> {
+    println!("depth {}: {}", a, tree.depth(a)?);
+    println!("depth {}: {}", b, tree.depth(b)?);
 
-impl System {
-    fn new() -> Self {
-        Self {
-            orbits: HashMap::new(),
-            distance: HashMap::new(),
+    match tree.path(a, b)? {
+        Some(path) => {
+            println!("common: {}", tree.lca(a, b)?.unwrap_or("-"));
+            println!("distance: {}", path.len() - 1);
+            println!("path: {}", path.join(" -> "));
         }
+        None => println!("{} and {} are not connected", a, b),
     }
 
-    fn add(&mut self, info: OrbitInfo) {
-        let OrbitInfo { primary, secondary } = info;
-        println!("primary: {}, secondary: {}", primary, secondary);
-        self.orbits.insert(secondary, primary);
-    }
-
-    fn update(&mut self, key: String) -> i32 {
-        println!("try to update {}", key);
-        if let Some(d) = self.distance.get(&key) {
-            return *d;
-        }
+    Ok(())
+}
 
-        let master = self.orbits.get(&key).map(|k| k.clone());
+fn main() -> Result<()> {
+    let raw = fs::read_to_string("input.txt")?;
+    let args = env::args().collect::<Vec<_>>();
 
-        if let Some(master) = master {
-            let distance = self.update(master.clone()) + 1;
-            println!("Set distance {} to {}", master, distance);
-            self.distance.insert(key, distance);
-            distance
-        } else {
-            0
+    if args.get(1).map(String::as_str) == Some("check") {
+        let problems = graph::validate(&raw, "COM");
+        for problem in problems.iter() {
+            println!("{}", problem);
         }
+        return match problems.len() {
+            0 => Ok(()),
+            n => Err(format!("{} problems found", n).into()),
+        };
     }
 
-    fn update_all(&mut self) {
-        let keys = self.orbits.keys().map(|k| k.clone()).collect::<Vec<_>>();
-        for k in keys {
-            self.update(k);
-        }
-    }
+    let list = OrbitInfo::parse_all(&raw)?;
+    let tree = OrbitTree::build(&list)?;
 
-    fn path_to(&self, input: &str) -> Vec<String> {
-        let mut result = vec![];
-        let mut step = input;
-        loop {
-            match self.orbits.get(step) {
-                Some(master) => {
-                    result.push(master.clone());
-                    step = master
-                }
-                None => break,
-            }
+    match args.get(1).map(String::as_str) {
+        Some("dot") => {
+            print!("{}", tree.to_dot());
+            return Ok(());
         }
-
-        result.reverse();
-        result
+        Some("query") => {
+            let a = args.get(2).ok_or("missing object")?;
+            let b = args.get(3).ok_or("missing object")?;
+            return query(&tree, a, b);
+        }
+        _ => {}
     }
-}
-
-fn first_diff(a: &[String], b: &[String]) -> usize {
-    let pairs = a.iter().zip(b.iter());
-    let index = pairs
-        .enumerate()
-        .find(|(_, pair)| pair.0 != pair.1)
-        .map(|(i, _)| i);
-
-    index.unwrap_or(a.len())
-}
-
-fn main() -> Result<()> {
-    let raw = fs::read_to_string("input.txt")?;
-    let mut list = raw
-        .lines()
-        .map(|line| OrbitInfo::parse(line))
-        .collect::<Result<Vec<_>>>()?;
-
-    let mut system = System::new();
 
-    list.drain(0..).for_each(|i| {
-        system.add(i);
-    });
-
-    system.update_all();
-    let total: i32 = system.distance.values().sum();
-    dbg!(total);
-
-    let you_path = system.path_to("YOU");
-    let san_path = system.path_to("SAN");
-
-    let x = first_diff(&you_path, &san_path);
-    let y = you_path.len() + san_path.len() - (x * 2);
-
-    dbg!(y);
+    println!("Task I : {}", tree.checksum());
+    match tree.transfers("YOU", "SAN")? {
+        Some(transfers) => println!("Task II: {}", transfers),
+        None => println!("Task II: not connected"),
+    }
 
     Ok(())
 }
@@ -138,4 +114,18 @@ mod test {
 
         assert_eq!(expected, OrbitInfo::parse(&input).unwrap());
     }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(OrbitInfo::parse("COMB").is_err());
+        assert!(OrbitInfo::parse("COM)").is_err());
+        assert!(OrbitInfo::parse("A)B)C").is_err());
+        assert!(OrbitInfo::parse("A) B").is_err());
+
+        let error = OrbitInfo::parse_all("COM)A\n\nA)B\nB-C\n").unwrap_err();
+        assert_eq!(
+            "line 4: expected PRIMARY)SECONDARY, got \"B-C\"",
+            error.to_string()
+        );
+    }
 }
@@ -0,0 +1,281 @@
+use crate::{OrbitInfo, Result};
+use std::collections::HashMap;
+
+// Objects are numbered in the order they first appear, `up[k][i]` is the
+// ancestor 2^k levels above object `i` (or the root of its tree).
+#[derive(Debug)]
+pub struct OrbitTree {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    parent: Vec<Option<usize>>,
+    depth: Vec<usize>,
+    up: Vec<Vec<usize>>,
+}
+
+impl OrbitTree {
+    pub fn build(orbits: &[OrbitInfo]) -> Result<Self> {
+        let mut names = vec![];
+        let mut index = HashMap::new();
+        let mut id = |name: &str| -> usize {
+            *index.entry(name.to_owned()).or_insert_with(|| {
+                names.push(name.to_owned());
+                names.len() - 1
+            })
+        };
+
+        let mut edges = vec![];
+        for info in orbits.iter() {
+            edges.push((id(&info.primary), id(&info.secondary)));
+        }
+
+        let mut parent = vec![None; names.len()];
+        let mut children = vec![vec![]; names.len()];
+        for (primary, secondary) in edges {
+            if let Some(other) = parent[secondary] {
+                return Err(format!(
+                    "{} orbits both {} and {}",
+                    names[secondary], names[other], names[primary]
+                )
+                .into());
+            }
+
+            parent[secondary] = Some(primary);
+            children[primary].push(secondary);
+        }
+
+        // breadth first from every root, whatever is left is on a cycle
+        let mut depth = vec![usize::MAX; names.len()];
+        let mut queue = (0..names.len())
+            .filter(|i| parent[*i].is_none())
+            .collect::<Vec<_>>();
+        queue.iter().for_each(|i| depth[*i] = 0);
+
+        let mut head = 0;
+        while let Some(object) = queue.get(head).copied() {
+            head += 1;
+            for child in children[object].iter() {
+                depth[*child] = depth[object] + 1;
+                queue.push(*child);
+            }
+        }
+
+        if let Some(i) = depth.iter().position(|d| *d == usize::MAX) {
+            return Err(format!("{} orbits itself through a cycle", names[i]).into());
+        }
+
+        let max_depth = depth.iter().max().copied().unwrap_or(0);
+        let levels = (usize::BITS - max_depth.leading_zeros()).max(1) as usize;
+
+        let mut up = vec![(0..names.len())
+            .map(|i| parent[i].unwrap_or(i))
+            .collect::<Vec<_>>()];
+        for k in 1..levels {
+            let prev = &up[k - 1];
+            let next = prev.iter().map(|p| prev[*p]).collect();
+            up.push(next);
+        }
+
+        Ok(Self {
+            names,
+            index,
+            parent,
+            depth,
+            up,
+        })
+    }
+
+    // Names in the order they first appear in the map.
+    pub fn objects(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+
+    fn id(&self, name: &str) -> Result<usize> {
+        self.index
+            .get(name)
+            .copied()
+            .ok_or_else(|| format!("unknown object {}", name).into())
+    }
+
+    // Number of direct and indirect orbits of all objects.
+    pub fn checksum(&self) -> usize {
+        self.depth.iter().sum()
+    }
+
+    pub fn depth(&self, name: &str) -> Result<usize> {
+        Ok(self.depth[self.id(name)?])
+    }
+
+    pub fn parent(&self, name: &str) -> Result<Option<&str>> {
+        Ok(self.parent[self.id(name)?].map(|p| self.names[p].as_str()))
+    }
+
+    fn ancestor(&self, mut object: usize, levels: usize) -> usize {
+        for (k, up) in self.up.iter().enumerate() {
+            if levels & (1 << k) != 0 {
+                object = up[object];
+            }
+        }
+
+        object
+    }
+
+    fn lca_id(&self, a: usize, b: usize) -> Option<usize> {
+        let (a, b) = if self.depth[a] < self.depth[b] {
+            (b, a)
+        } else {
+            (a, b)
+        };
+
+        let mut a = self.ancestor(a, self.depth[a] - self.depth[b]);
+        let mut b = b;
+        if a == b {
+            return Some(a);
+        }
+
+        for up in self.up.iter().rev() {
+            if up[a] != up[b] {
+                a = up[a];
+                b = up[b];
+            }
+        }
+
+        // different trees end up at their own roots
+        self.parent[a].filter(|p| Some(*p) == self.parent[b])
+    }
+
+    // Lowest common ancestor, None when the objects are in different trees.
+    pub fn lca(&self, a: &str, b: &str) -> Result<Option<&str>> {
+        let lca = self.lca_id(self.id(a)?, self.id(b)?);
+        Ok(lca.map(|i| self.names[i].as_str()))
+    }
+
+    // Number of orbits between two objects.
+    pub fn distance(&self, a: &str, b: &str) -> Result<Option<usize>> {
+        let (a, b) = (self.id(a)?, self.id(b)?);
+        let lca = self.lca_id(a, b);
+        Ok(lca.map(|c| self.depth[a] + self.depth[b] - 2 * self.depth[c]))
+    }
+
+    // Objects from `a` to `b` both included.
+    pub fn path(&self, a: &str, b: &str) -> Result<Option<Vec<&str>>> {
+        let (a, b) = (self.id(a)?, self.id(b)?);
+        let lca = match self.lca_id(a, b) {
+            Some(lca) => lca,
+            None => return Ok(None),
+        };
+
+        let climb = |mut object: usize| {
+            let mut result = vec![object];
+            while object != lca {
+                object = self.up[0][object];
+                result.push(object);
+            }
+            result
+        };
+
+        let mut result = climb(a);
+        let mut back = climb(b);
+        back.pop();
+        result.extend(back.into_iter().rev());
+
+        Ok(Some(
+            result.into_iter().map(|i| self.names[i].as_str()).collect(),
+        ))
+    }
+
+    // Orbital transfers needed to move from the object `a` orbits to the one
+    // `b` orbits.
+    pub fn transfers(&self, a: &str, b: &str) -> Result<Option<usize>> {
+        let a = self
+            .parent(a)?
+            .ok_or_else(|| format!("{} orbits nothing", a))?;
+        let b = self
+            .parent(b)?
+            .ok_or_else(|| format!("{} orbits nothing", b))?;
+        self.distance(a, b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tree(input: &str) -> OrbitTree {
+        OrbitTree::build(&OrbitInfo::parse_all(input).unwrap()).unwrap()
+    }
+
+    fn example() -> OrbitTree {
+        tree("COM)B\nB)C\nC)D\nD)E\nE)F\nB)G\nG)H\nD)I\nE)J\nJ)K\nK)L\nK)YOU\nI)SAN\n")
+    }
+
+    #[test]
+    fn test_checksum() {
+        let tree = tree("COM)B\nB)C\nC)D\nD)E\nE)F\nB)G\nG)H\nD)I\nE)J\nJ)K\nK)L");
+        assert_eq!(42, tree.checksum());
+        assert_eq!(7, tree.depth("L").unwrap());
+        assert_eq!(0, tree.depth("COM").unwrap());
+        assert!(tree.depth("X").is_err());
+    }
+
+    #[test]
+    fn test_lca() {
+        let tree = example();
+
+        assert_eq!(Some("D"), tree.lca("YOU", "SAN").unwrap());
+        assert_eq!(Some("B"), tree.lca("H", "L").unwrap());
+        assert_eq!(Some("E"), tree.lca("E", "L").unwrap());
+        assert_eq!(Some("COM"), tree.lca("COM", "COM").unwrap());
+    }
+
+    #[test]
+    fn test_transfers() {
+        let tree = example();
+
+        assert_eq!(Some(4), tree.transfers("YOU", "SAN").unwrap());
+        assert_eq!(Some(6), tree.distance("YOU", "SAN").unwrap());
+        assert_eq!(
+            Some(vec!["YOU", "K", "J", "E", "D", "I", "SAN"]),
+            tree.path("YOU", "SAN").unwrap()
+        );
+        assert_eq!(Some(vec!["L"]), tree.path("L", "L").unwrap());
+        assert_eq!(Some(vec!["C", "D", "E"]), tree.path("C", "E").unwrap());
+        assert!(tree.transfers("COM", "SAN").is_err());
+    }
+
+    #[test]
+    fn test_forest() {
+        let tree = tree("COM)A\nA)B\nX)Y\nY)Z");
+
+        assert_eq!(None, tree.lca("B", "Z").unwrap());
+        assert_eq!(None, tree.distance("B", "Z").unwrap());
+        assert_eq!(None, tree.path("B", "Z").unwrap());
+        assert_eq!(Some("COM"), tree.lca("A", "COM").unwrap());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let build = |input| OrbitTree::build(&OrbitInfo::parse_all(input).unwrap());
+
+        assert!(build("COM)A\nB)A").is_err());
+        assert!(build("COM)A\nB)C\nC)B").is_err());
+    }
+
+    #[test]
+    fn test_deep() {
+        // a chain far deeper than any recursion could go
+        let input = (0..200_000)
+            .map(|i| format!("N{})N{}", i, i + 1))
+            .chain((0..1000).map(|i| format!("N{})M{}", i * 150, i)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let tree = tree(&input);
+
+        assert_eq!(200_000, tree.depth("N200000").unwrap());
+        assert_eq!(Some("N149850"), tree.lca("M999", "N200000").unwrap());
+        assert_eq!(Some(1 + 150 + 1), tree.distance("M998", "M999").unwrap());
+        assert_eq!(
+            Some(200_000 - 149_850 + 1),
+            tree.distance("M999", "N200000").unwrap()
+        );
+    }
+}
@@ -2,6 +2,9 @@ use crate::vm::CPU;
 use permutohedron::heap_recursive;
 use std::fs;
 
+#[cfg(test)]
+#[path = "../../intcode/conformance.rs"]
+mod conformance;
 mod vm;
 
 fn parse_programm(input: &str) -> Vec<i32> {
@@ -0,0 +1,470 @@
+use crate::disasm;
+use crate::vm::{parse_programm, CPU};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+const THREAD_ID: i64 = 1;
+const SOURCE_REF: i64 = 1;
+const REGISTERS_REF: i64 = 1;
+const MEMORY_REF: i64 = 2;
+
+// Debug Adapter Protocol session. The disassembly of the launched program is
+// served as a virtual source, so breakpoints are set on its lines.
+pub struct Session {
+    cpu: Option<CPU>,
+    listing: Vec<(usize, String)>,
+    breakpoints: HashSet<usize>,
+    stop_on_entry: bool,
+    halted: bool,
+    printed: usize,
+    seq: i64,
+    pub done: bool,
+}
+
+fn source() -> Value {
+    json!({ "name": "intcode", "sourceReference": SOURCE_REF })
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            cpu: None,
+            listing: vec![],
+            breakpoints: HashSet::new(),
+            stop_on_entry: false,
+            halted: false,
+            printed: 0,
+            seq: 0,
+            done: false,
+        }
+    }
+
+    pub fn handle(&mut self, request: &Value) -> Vec<Value> {
+        let command = request["command"].as_str().unwrap_or("");
+        let args = &request["arguments"];
+        let mut messages = vec![];
+
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsSteppingGranularity": false,
+            })),
+            "launch" => self.launch(args),
+            "setBreakpoints" => Ok(self.set_breakpoints(args)),
+            "configurationDone" | "threads" | "stackTrace" | "scopes" | "variables"
+            | "source" | "continue" | "next" | "stepIn" | "stepOut"
+                if self.cpu.is_none() =>
+            {
+                Err("program is not launched".to_owned())
+            }
+            "configurationDone" | "continue" | "next" | "stepIn" | "stepOut" => Ok(json!({})),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "cpu" }] })),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => Ok(self.scopes()),
+            "variables" => Ok(self.variables(args)),
+            "source" => Ok(json!({ "content": self.source_text() })),
+            "disconnect" => {
+                self.done = true;
+                Ok(json!({}))
+            }
+            _ => Err(format!("unsupported command: {}", command)),
+        };
+
+        messages.push(self.response(request, result));
+
+        match command {
+            "initialize" => messages.push(self.event("initialized", json!({}))),
+            "configurationDone" if self.cpu.is_some() => {
+                let entry = self.cpu.as_ref().map(|cpu| cpu.ip());
+                if self.stop_on_entry {
+                    messages.push(self.stopped("entry", None));
+                } else if entry.is_some_and(|ip| self.breakpoints.contains(&ip)) {
+                    // resuming steps first, so a breakpoint on the entry is checked here
+                    messages.push(self.stopped("breakpoint", None));
+                } else {
+                    self.resume(&mut messages, false);
+                }
+            }
+            "continue" if self.cpu.is_some() => self.resume(&mut messages, false),
+            "next" | "stepIn" | "stepOut" if self.cpu.is_some() => {
+                self.resume(&mut messages, true)
+            }
+            _ => {}
+        }
+
+        messages
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<Value, String> {
+        let programm = match (args["code"].as_str(), args["program"].as_str()) {
+            (Some(code), _) => parse_programm(code),
+            (None, Some(path)) => {
+                let raw = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+                parse_programm(&raw)
+            }
+            _ => return Err("either `program` or `code` is required".to_owned()),
+        };
+
+        let input = args["input"]
+            .as_array()
+            .map(|values| values.iter().filter_map(|v| v.as_i64()).collect())
+            .unwrap_or_default();
+
+        self.listing = disasm::listing(&programm);
+        self.cpu = Some(CPU::new(programm, input));
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        self.halted = false;
+        self.printed = 0;
+
+        Ok(json!({}))
+    }
+
+    fn set_breakpoints(&mut self, args: &Value) -> Value {
+        let lines: Vec<i64> = args["breakpoints"]
+            .as_array()
+            .map(|bps| bps.iter().filter_map(|bp| bp["line"].as_i64()).collect())
+            .unwrap_or_default();
+
+        self.breakpoints.clear();
+
+        let breakpoints = lines
+            .iter()
+            .map(|line| match self.addr_of_line(*line) {
+                Some(addr) => {
+                    self.breakpoints.insert(addr);
+                    json!({ "verified": true, "line": line, "source": source() })
+                }
+                None => json!({ "verified": false, "line": line, "message": "no such line" }),
+            })
+            .collect::<Vec<_>>();
+
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn addr_of_line(&self, line: i64) -> Option<usize> {
+        if line < 1 {
+            return None;
+        }
+
+        self.listing.get(line as usize - 1).map(|(addr, _)| *addr)
+    }
+
+    // Lines are 1-based; an `ip` that points into the middle of a listed
+    // instruction (self-modifying code) is shown on the enclosing line.
+    fn line_of_addr(&self, addr: usize) -> usize {
+        match self.listing.binary_search_by_key(&addr, |(a, _)| *a) {
+            Ok(i) => i + 1,
+            Err(i) => i.max(1),
+        }
+    }
+
+    fn source_text(&self) -> String {
+        self.listing
+            .iter()
+            .map(|(addr, line)| format!("{:5}: {}\n", addr, line))
+            .collect()
+    }
+
+    fn stack_trace(&self) -> Value {
+        let cpu = self.cpu.as_ref().unwrap();
+        let ip = cpu.ip();
+        let name = disasm::Op::decode(cpu.memory(), ip)
+            .map(|op| op.to_string())
+            .unwrap_or_else(|| format!(".data {}", cpu.peek(ip)));
+
+        json!({
+            "stackFrames": [{
+                "id": 1,
+                "name": name,
+                "line": self.line_of_addr(ip),
+                "column": 1,
+                "source": source(),
+                "instructionPointerReference": ip.to_string(),
+            }],
+            "totalFrames": 1,
+        })
+    }
+
+    fn scopes(&self) -> Value {
+        let cpu = self.cpu.as_ref().unwrap();
+
+        json!({
+            "scopes": [
+                {
+                    "name": "Registers",
+                    "variablesReference": REGISTERS_REF,
+                    "expensive": false,
+                },
+                {
+                    "name": "Memory",
+                    "variablesReference": MEMORY_REF,
+                    "indexedVariables": cpu.memory().len(),
+                    "expensive": true,
+                },
+            ]
+        })
+    }
+
+    fn variables(&self, args: &Value) -> Value {
+        let cpu = self.cpu.as_ref().unwrap();
+        let variable = |name: String, value: i64| {
+            json!({ "name": name, "value": value.to_string(), "variablesReference": 0 })
+        };
+
+        let variables = match args["variablesReference"].as_i64() {
+            Some(REGISTERS_REF) => vec![
+                variable("ip".to_owned(), cpu.ip() as i64),
+                variable("rb".to_owned(), cpu.rb()),
+                variable("ticks".to_owned(), cpu.info().ticks as i64),
+            ],
+            Some(MEMORY_REF) => {
+                let start = args["start"].as_u64().unwrap_or(0) as usize;
+                let count = args["count"].as_u64().map(|c| c as usize);
+                let len = cpu.memory().len();
+                let end = count
+                    .and_then(|c| start.checked_add(c))
+                    .map_or(len, |end| end.min(len));
+
+                (start..end)
+                    .map(|addr| variable(format!("[{}]", addr), cpu.peek(addr)))
+                    .collect()
+            }
+            _ => vec![],
+        };
+
+        json!({ "variables": variables })
+    }
+
+    fn resume(&mut self, messages: &mut Vec<Value>, single: bool) {
+        if self.halted {
+            return;
+        }
+
+        let cpu = self.cpu.as_mut().unwrap();
+        let mut reason = None;
+
+        loop {
+            if cpu.waits_input() {
+                reason = Some(("exception", Some("waiting for input")));
+                break;
+            }
+
+            if !cpu.step() {
+                self.halted = true;
+                break;
+            }
+
+            if single {
+                reason = Some(("step", None));
+                break;
+            }
+
+            if self.breakpoints.contains(&cpu.ip()) {
+                reason = Some(("breakpoint", None));
+                break;
+            }
+        }
+
+        let output = cpu.output[self.printed..].to_vec();
+        self.printed += output.len();
+
+        for value in output {
+            let body = json!({ "category": "stdout", "output": format!("{}\n", value) });
+            messages.push(self.event("output", body));
+        }
+
+        match reason {
+            Some((reason, text)) => messages.push(self.stopped(reason, text)),
+            None => {
+                messages.push(self.event("exited", json!({ "exitCode": 0 })));
+                messages.push(self.event("terminated", json!({})));
+            }
+        }
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<&str>) -> Value {
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+
+        if let Some(text) = text {
+            body["text"] = json!(text);
+        }
+
+        self.event("stopped", body)
+    }
+
+    fn next_seq(&mut self) -> i64 {
+        self.seq += 1;
+        self.seq
+    }
+
+    fn response(&mut self, request: &Value, result: Result<Value, String>) -> Value {
+        let mut response = json!({
+            "seq": self.next_seq(),
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+        });
+
+        match result {
+            Ok(body) => {
+                response["success"] = json!(true);
+                response["body"] = body;
+            }
+            Err(message) => {
+                response["success"] = json!(false);
+                response["message"] = json!(message);
+            }
+        }
+
+        response
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> Value {
+        json!({
+            "seq": self.next_seq(),
+            "type": "event",
+            "event": event,
+            "body": body,
+        })
+    }
+}
+
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+pub fn serve(mut reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+    let mut session = Session::new();
+
+    while let Some(request) = read_message(&mut reader)? {
+        for message in session.handle(&request) {
+            write_message(&mut writer, &message)?;
+        }
+
+        if session.done {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::path::Path;
+
+    // Every key of `expected` must be present in `actual` with a matching
+    // value, arrays must match element by element.
+    fn matches(expected: &Value, actual: &Value) -> bool {
+        match (expected, actual) {
+            (Value::Object(e), Value::Object(a)) => e
+                .iter()
+                .all(|(k, v)| a.get(k).is_some_and(|av| matches(v, av))),
+            (Value::Array(e), Value::Array(a)) => {
+                e.len() == a.len() && e.iter().zip(a.iter()).all(|(e, a)| matches(e, a))
+            }
+            _ => expected == actual,
+        }
+    }
+
+    // A transcript is a list of `{"send": request}` and `{"recv": message}`
+    // entries; every produced message has to be expected, in order.
+    fn replay(name: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("dap").join(name);
+        let raw = fs::read_to_string(&path).unwrap();
+        let transcript: Vec<Value> = serde_json::from_str(&raw).unwrap();
+
+        let mut session = Session::new();
+        let mut produced = VecDeque::new();
+
+        for (i, entry) in transcript.iter().enumerate() {
+            if let Some(request) = entry.get("send") {
+                assert!(produced.is_empty(), "{} #{}: unexpected {:?}", name, i, produced);
+                produced.extend(session.handle(request));
+            } else if let Some(expected) = entry.get("recv") {
+                let actual = produced
+                    .pop_front()
+                    .unwrap_or_else(|| panic!("{} #{}: no message for {}", name, i, expected));
+                assert!(
+                    matches(expected, &actual),
+                    "{} #{}: expected {}, got {}",
+                    name,
+                    i,
+                    expected,
+                    actual
+                );
+            }
+        }
+
+        assert!(produced.is_empty(), "{}: unexpected {:?}", name, produced);
+    }
+
+    #[test]
+    fn test_framing() {
+        let request = json!({ "seq": 1, "type": "request", "command": "initialize" });
+        let mut raw = vec![];
+        write_message(&mut raw, &request).unwrap();
+
+        let mut reader = io::Cursor::new(raw);
+        assert_eq!(Some(request), read_message(&mut reader).unwrap());
+        assert_eq!(None, read_message(&mut reader).unwrap());
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        replay("breakpoints.json");
+    }
+
+    #[test]
+    fn test_stepping() {
+        replay("stepping.json");
+    }
+
+    #[test]
+    fn test_input() {
+        replay("input.json");
+    }
+
+    #[test]
+    fn test_entry_breakpoint() {
+        replay("entry.json");
+    }
+}
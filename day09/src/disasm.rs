@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+pub const POSITION: u8 = 0;
+pub const IMMEDIATE: u8 = 1;
+pub const RELATIVE: u8 = 2;
+
+pub const ADD: i64 = 1;
+pub const MUL: i64 = 2;
+pub const INPUT: i64 = 3;
+pub const OUTPUT: i64 = 4;
+pub const JUMP_TRUE: i64 = 5;
+pub const JUMP_FALSE: i64 = 6;
+pub const LESS_THAN: i64 = 7;
+pub const EQUALS: i64 = 8;
+pub const UPDATE_RELATIVE: i64 = 9;
+pub const HALT: i64 = 99;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Param {
+    pub mode: u8,
+    pub value: i64,
+}
+
+impl Param {
+    pub fn immediate(value: i64) -> Self {
+        Self {
+            mode: IMMEDIATE,
+            value,
+        }
+    }
+
+    pub fn is_const(&self, value: i64) -> bool {
+        self.mode == IMMEDIATE && self.value == value
+    }
+
+    pub fn address(&self) -> Option<usize> {
+        if self.mode == POSITION && self.value >= 0 {
+            Some(self.value as usize)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Op {
+    pub addr: usize,
+    pub opcode: i64,
+    pub params: Vec<Param>,
+}
+
+impl Op {
+    pub fn decode(programm: &[i64], addr: usize) -> Option<Self> {
+        let code = *programm.get(addr)?;
+        if code < 0 {
+            return None;
+        }
+
+        let (size, write) = match code % 100 {
+            ADD | MUL | LESS_THAN | EQUALS => (3, Some(2)),
+            INPUT => (1, Some(0)),
+            OUTPUT | UPDATE_RELATIVE => (1, None),
+            JUMP_TRUE | JUMP_FALSE => (2, None),
+            HALT => (0, None),
+            _ => return None,
+        };
+
+        let mut params = vec![];
+        for i in 0..size {
+            let mode = (code / 10_i64.pow(i as u32 + 2) % 10) as u8;
+            let value = *programm.get(addr + 1 + i)?;
+
+            if mode > RELATIVE || (write == Some(i) && mode == IMMEDIATE) {
+                return None;
+            }
+
+            params.push(Param { mode, value });
+        }
+
+        Some(Self {
+            addr,
+            opcode: code % 100,
+            params,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<i64> {
+        let code = self
+            .params
+            .iter()
+            .enumerate()
+            .fold(self.opcode, |acc, (i, param)| {
+                acc + param.mode as i64 * 10_i64.pow(i as u32 + 2)
+            });
+
+        let mut result = vec![code];
+        result.extend(self.params.iter().map(|p| p.value));
+        result
+    }
+
+    pub fn size(&self) -> usize {
+        self.params.len() + 1
+    }
+
+    pub fn cells(&self) -> Range<usize> {
+        self.addr..self.addr + self.size()
+    }
+
+    pub fn next(&self) -> usize {
+        self.addr + self.size()
+    }
+
+    pub fn is_jump(&self) -> bool {
+        self.opcode == JUMP_TRUE || self.opcode == JUMP_FALSE
+    }
+
+    // `Some(true)` for a jump that is always taken, `Some(false)` for one that
+    // never is, `None` if it depends on the memory.
+    pub fn jump_taken(&self) -> Option<bool> {
+        let cond = self.params[0];
+        match (self.opcode, cond.mode) {
+            (JUMP_TRUE, IMMEDIATE) => Some(cond.value != 0),
+            (JUMP_FALSE, IMMEDIATE) => Some(cond.value == 0),
+            _ => None,
+        }
+    }
+
+    pub fn static_target(&self) -> Option<usize> {
+        let target = self.params[1];
+        if target.mode == IMMEDIATE && target.value >= 0 {
+            Some(target.value as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn unconditional_target(&self) -> Option<usize> {
+        if self.is_jump() && self.jump_taken() == Some(true) {
+            self.static_target()
+        } else {
+            None
+        }
+    }
+
+    pub fn uses_relative(&self) -> bool {
+        self.opcode == UPDATE_RELATIVE || self.params.iter().any(|p| p.mode == RELATIVE)
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            POSITION => write!(f, "[{}]", self.value),
+            IMMEDIATE => write!(f, "#{}", self.value),
+            _ if self.value < 0 => write!(f, "[rb{}]", self.value),
+            _ => write!(f, "[rb+{}]", self.value),
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let p = &self.params;
+        match self.opcode {
+            ADD => write!(f, "add {}, {} -> {}", p[0], p[1], p[2]),
+            MUL => write!(f, "mul {}, {} -> {}", p[0], p[1], p[2]),
+            INPUT => write!(f, "in -> {}", p[0]),
+            OUTPUT => write!(f, "out {}", p[0]),
+            JUMP_TRUE => write!(f, "jt {}, {}", p[0], p[1]),
+            JUMP_FALSE => write!(f, "jf {}, {}", p[0], p[1]),
+            LESS_THAN => write!(f, "lt {}, {} -> {}", p[0], p[1], p[2]),
+            EQUALS => write!(f, "eq {}, {} -> {}", p[0], p[1], p[2]),
+            UPDATE_RELATIVE => write!(f, "arb {}", p[0]),
+            _ => write!(f, "hlt"),
+        }
+    }
+}
+
+// Follows the control flow from address 0. Jumps through memory can't be
+// followed, so code reachable only that way stays unknown and the program is
+// never compacted.
+pub fn trace(programm: &[i64]) -> BTreeMap<usize, Op> {
+    let mut ops = BTreeMap::new();
+    let mut queue = vec![0];
+
+    while let Some(addr) = queue.pop() {
+        if ops.contains_key(&addr) {
+            continue;
+        }
+
+        let op = match Op::decode(programm, addr) {
+            Some(op) => op,
+            None => continue,
+        };
+
+        if op.is_jump() {
+            if let Some(target) = op.static_target() {
+                queue.push(target);
+            }
+
+            if op.jump_taken() != Some(true) {
+                queue.push(op.next());
+            }
+        } else if op.opcode != HALT {
+            queue.push(op.next());
+        }
+
+        ops.insert(addr, op);
+    }
+
+    ops
+}
+
+
+// One line per instruction, cells that don't decode are shown as data.
+// Instructions found by `trace` win over a plain linear sweep, so data mixed
+// into the code doesn't shift the rest of the listing.
+pub fn listing(programm: &[i64]) -> Vec<(usize, String)> {
+    let traced = trace(programm);
+    let mut result = vec![];
+    let mut addr = 0;
+
+    while addr < programm.len() {
+        let op = traced.get(&addr).cloned().or_else(|| {
+            Op::decode(programm, addr)
+                .filter(|op| !op.cells().skip(1).any(|cell| traced.contains_key(&cell)))
+        });
+
+        match op {
+            Some(op) => {
+                result.push((addr, op.to_string()));
+                addr = op.next();
+            }
+            None => {
+                result.push((addr, format!(".data {}", programm[addr])));
+                addr += 1;
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let programm = vec![21101, 3, 4, -1];
+        let op = Op::decode(&programm, 0).unwrap();
+
+        assert_eq!(ADD, op.opcode);
+        assert_eq!(programm, op.encode());
+    }
+
+    #[test]
+    fn test_display() {
+        let programm = vec![21101, 3, 4, -1, 204, 2, 1006, 7, 0, 99];
+        let ops = trace(&programm);
+
+        assert_eq!("add #3, #4 -> [rb-1]", ops[&0].to_string());
+        assert_eq!("out [rb+2]", ops[&4].to_string());
+        assert_eq!("jf [7], #0", ops[&6].to_string());
+    }
+
+    #[test]
+    fn test_listing() {
+        let programm = vec![1105, 1, 4, 7, 104, 1, 99];
+        let lines = listing(&programm)
+            .into_iter()
+            .map(|(addr, line)| format!("{}: {}", addr, line))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec!["0: jt #1, #4", "3: .data 7", "4: out #1", "6: hlt"],
+            lines
+        );
+    }
+}
@@ -1,8 +1,16 @@
+use std::env;
 use std::fs;
+use std::io;
 use std::time::Instant;
 use vm::{CPUInfo, CPU};
 
+mod dap;
+mod disasm;
+mod optimizer;
 mod vm;
+#[cfg(test)]
+#[path = "../../intcode/conformance.rs"]
+mod conformance;
 
 fn result(tag: &str, result: i64, info: CPUInfo) {
     println!(
@@ -11,10 +19,46 @@ fn result(tag: &str, result: i64, info: CPUInfo) {
     );
 }
 
+fn optimize(raw: &str) {
+    let programm = vm::parse_programm(raw);
+    let optimized = optimizer::optimize(&programm);
+    let valid = optimizer::validate(&programm, &optimized.programm, &[vec![1], vec![2]]);
+
+    let code = optimized
+        .programm
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let moved = optimized
+        .map
+        .iter()
+        .enumerate()
+        .filter(|(old, new)| old != *new)
+        .count();
+
+    println!("{}", code);
+    eprintln!(
+        "regions: {:?}, rewritten: {}, removed: {}, moved: {}, valid: {}",
+        optimized.regions, optimized.rewritten, optimized.removed, moved, valid
+    );
+}
+
 fn main() {
     let now = Instant::now();
 
     let raw = fs::read_to_string("input.txt").expect("cant read");
+
+    match env::args().nth(1).as_deref() {
+        Some("optimize") => return optimize(&raw),
+        Some("dap") => {
+            let stdin = io::stdin();
+            return dap::serve(stdin.lock(), io::stdout()).expect("dap session failed");
+        }
+        _ => {}
+    }
+
     let mut cpu = CPU::new_from_str(&raw, vec![1]);
     cpu.run();
     let task_a = (cpu.output[0], cpu.info());
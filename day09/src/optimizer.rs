@@ -0,0 +1,393 @@
+use crate::disasm::*;
+use crate::vm::CPU;
+use std::collections::{BTreeMap, HashSet};
+use std::ops::Range;
+
+impl Op {
+    fn is_noop(&self) -> bool {
+        match self.opcode {
+            ADD | MUL => {
+                let neutral = if self.opcode == ADD { 0 } else { 1 };
+                let (a, b, c) = (self.params[0], self.params[1], self.params[2]);
+                (a == c && b.is_const(neutral)) || (b == c && a.is_const(neutral))
+            }
+            JUMP_TRUE | JUMP_FALSE => match self.jump_taken() {
+                Some(false) => true,
+                Some(true) => self.static_target() == Some(self.next()),
+                None => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+pub struct Optimized {
+    pub programm: Vec<i64>,
+    // old address -> new address, cells of removed instructions point to the
+    // instruction that follows them
+    pub map: Vec<usize>,
+    pub regions: Vec<Range<usize>>,
+    pub rewritten: usize,
+    pub removed: usize,
+}
+
+struct Analysis {
+    ops: BTreeMap<usize, Op>,
+    stable: HashSet<usize>,
+    dynamic_jumps: bool,
+    relative: bool,
+}
+
+const PROLOGUE_STEPS: usize = 10_000;
+
+// Lowest address relative mode may reach, `None` if it's never used. Up to the
+// first input the run is the same for every input, so the base is followed
+// there step by step; after that it's assumed not to drop below where it
+// was, which holds for a stack kept past the code.
+fn relative_floor(programm: &[i64], ops: &[Op]) -> Option<i64> {
+    let offset = ops
+        .iter()
+        .flat_map(|op| op.params.iter())
+        .filter(|p| p.mode == RELATIVE)
+        .map(|p| p.value)
+        .min()?;
+
+    let mut cpu = CPU::new(programm.to_vec(), vec![]);
+    let mut floor = i64::MAX;
+
+    for _ in 0..PROLOGUE_STEPS {
+        if cpu.waits_input() {
+            break;
+        }
+
+        let op = match Op::decode(cpu.memory(), cpu.ip()) {
+            Some(op) => op,
+            None => break,
+        };
+
+        for p in op.params.iter().filter(|p| p.mode == RELATIVE) {
+            floor = floor.min(cpu.rb() + p.value);
+        }
+
+        if !cpu.step() {
+            return Some(floor);
+        }
+    }
+
+    Some(floor.min(cpu.rb() + offset))
+}
+
+// An instruction is stable when nothing reads or writes its cells as data and
+// it doesn't overlap with another instruction, so it may be rewritten freely.
+// Cells relative mode may reach count as data. Code only reachable through
+// memory is unknown, so every cell outside the traced code is decoded as well
+// and a value pointing into the middle of an instruction may be a jump target.
+fn analyze(programm: &[i64]) -> Analysis {
+    let ops = trace(programm);
+
+    let dynamic_jumps = ops
+        .values()
+        .any(|op| op.is_jump() && op.static_target().is_none());
+
+    let relative = ops.values().any(|op| op.uses_relative());
+
+    let mut owners = vec![0; programm.len()];
+    for op in ops.values() {
+        for cell in op.cells() {
+            owners[cell] += 1;
+        }
+    }
+
+    let untraced = (0..programm.len())
+        .filter(|addr| dynamic_jumps && owners[*addr] == 0)
+        .filter_map(|addr| Op::decode(programm, addr))
+        .collect::<Vec<_>>();
+
+    let all = ops
+        .values()
+        .chain(untraced.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let referenced = all
+        .iter()
+        .flat_map(|op| op.params.iter().filter_map(|p| p.address()))
+        .collect::<HashSet<_>>();
+
+    let floor = relative_floor(programm, &all).unwrap_or(i64::MAX);
+
+    let targets = programm
+        .iter()
+        .filter(|_| dynamic_jumps)
+        .filter(|value| **value >= 0)
+        .map(|value| *value as usize)
+        .collect::<HashSet<_>>();
+
+    let stable = ops
+        .values()
+        .filter(|op| (op.next() as i64) <= floor)
+        .filter(|op| op.cells().skip(1).all(|cell| !targets.contains(&cell)))
+        .filter(|op| {
+            op.cells()
+                .all(|cell| owners[cell] == 1 && !referenced.contains(&cell))
+        })
+        .map(|op| op.addr)
+        .collect();
+
+    Analysis {
+        ops,
+        stable,
+        dynamic_jumps,
+        relative,
+    }
+}
+
+fn regions(analysis: &Analysis) -> Vec<Range<usize>> {
+    let mut result: Vec<Range<usize>> = vec![];
+
+    for op in analysis.ops.values() {
+        if !analysis.stable.contains(&op.addr) {
+            continue;
+        }
+
+        match result.last_mut() {
+            Some(last) if last.end == op.addr => last.end = op.next(),
+            _ => result.push(op.cells()),
+        }
+    }
+
+    result
+}
+
+fn rewrite(op: &Op, ops: &BTreeMap<usize, Op>, stable: &HashSet<usize>) -> Op {
+    let mut op = op.clone();
+
+    match op.opcode {
+        MUL if op.params[0].is_const(0) || op.params[1].is_const(0) => {
+            op.opcode = ADD;
+            op.params[0] = Param::immediate(0);
+            op.params[1] = Param::immediate(0);
+        }
+        LESS_THAN | EQUALS if op.params[..2].iter().all(|p| p.mode == IMMEDIATE) => {
+            let (a, b) = (op.params[0].value, op.params[1].value);
+            let value = if op.opcode == LESS_THAN { a < b } else { a == b };
+
+            op.opcode = ADD;
+            op.params[0] = Param::immediate(value as i64);
+            op.params[1] = Param::immediate(0);
+        }
+        JUMP_TRUE | JUMP_FALSE if op.jump_taken() == Some(true) => {
+            op.opcode = JUMP_TRUE;
+            op.params[0] = Param::immediate(1);
+
+            let mut seen = HashSet::new();
+            while let Some(target) = op.static_target() {
+                let next = ops
+                    .get(&target)
+                    .filter(|next| stable.contains(&next.addr))
+                    .and_then(|next| next.unconditional_target());
+
+                match next {
+                    Some(next) if seen.insert(target) => op.params[1].value = next as i64,
+                    _ => break,
+                }
+            }
+        }
+        _ => {}
+    }
+
+    op
+}
+
+fn relocate(op: &Op, map: &[usize]) -> Op {
+    let mut op = op.clone();
+    let is_jump = op.is_jump();
+
+    for (i, param) in op.params.iter_mut().enumerate() {
+        let target = is_jump && i == 1 && param.mode == IMMEDIATE;
+        if (param.mode == POSITION || target) && param.value >= 0 {
+            if let Some(addr) = map.get(param.value as usize) {
+                param.value = *addr as i64;
+            }
+        }
+    }
+
+    op
+}
+
+// Rewrites stable instructions into cheaper forms. No-op instructions are
+// removed only when the whole program is statically known: no jumps through
+// memory, no relative mode and no instruction used as data. The result keeps
+// the original length so memory past the end stays at the same addresses.
+pub fn optimize(programm: &[i64]) -> Optimized {
+    let analysis = analyze(programm);
+
+    let ops = analysis
+        .ops
+        .values()
+        .map(|op| {
+            if analysis.stable.contains(&op.addr) {
+                rewrite(op, &analysis.ops, &analysis.stable)
+            } else {
+                op.clone()
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let rewritten = ops
+        .iter()
+        .filter(|op| analysis.ops.get(&op.addr) != Some(op))
+        .count();
+
+    let compact = !analysis.dynamic_jumps
+        && !analysis.relative
+        && analysis.stable.len() == analysis.ops.len();
+
+    let removed = ops
+        .iter()
+        .filter(|op| compact && op.is_noop())
+        .map(|op| op.addr)
+        .collect::<HashSet<_>>();
+
+    let mut skip = vec![false; programm.len()];
+    for op in ops.iter().filter(|op| removed.contains(&op.addr)) {
+        for cell in op.cells() {
+            skip[cell] = true;
+        }
+    }
+
+    let mut map = vec![0; programm.len()];
+    let mut counter = 0;
+    for (addr, skipped) in skip.iter().enumerate() {
+        map[addr] = counter;
+        if !skipped {
+            counter += 1;
+        }
+    }
+
+    let mut result = programm.to_vec();
+    for op in ops.iter() {
+        result.splice(op.cells(), relocate(op, &map).encode());
+    }
+
+    let mut result = result
+        .into_iter()
+        .zip(skip.iter())
+        .filter(|(_, skipped)| !*skipped)
+        .map(|(value, _)| value)
+        .collect::<Vec<_>>();
+    result.resize(programm.len(), 0);
+
+    Optimized {
+        programm: result,
+        map,
+        regions: regions(&analysis),
+        rewritten,
+        removed: removed.len(),
+    }
+}
+
+fn run(programm: &[i64], input: &[i64]) -> Vec<i64> {
+    let mut cpu = CPU::new(programm.to_vec(), input.to_vec());
+    cpu.run();
+    cpu.output
+}
+
+pub fn validate(original: &[i64], optimized: &[i64], inputs: &[Vec<i64>]) -> bool {
+    inputs
+        .iter()
+        .all(|input| run(original, input) == run(optimized, input))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vm::parse_programm;
+    use std::fs;
+
+    #[test]
+    fn test_regions() {
+        let programm = vec![1101, 1, 1, 5, 104, 0, 99];
+        let optimized = optimize(&programm);
+
+        assert_eq!(vec![0..4, 6..7], optimized.regions);
+        assert_eq!(programm, optimized.programm);
+    }
+
+    #[test]
+    fn test_fold_jump() {
+        let programm = vec![1106, 0, 4, 99, 104, 7, 99];
+        let optimized = optimize(&programm);
+
+        assert_eq!(vec![1105, 1, 4, 99, 104, 7, 99], optimized.programm);
+        assert_eq!(1, optimized.rewritten);
+    }
+
+    #[test]
+    fn test_fold_mul_zero() {
+        let programm = vec![1002, 7, 0, 7, 4, 7, 99, 5];
+        let optimized = optimize(&programm);
+
+        assert_eq!(vec![1101, 0, 0, 7, 4, 7, 99, 5], optimized.programm);
+    }
+
+    #[test]
+    fn test_remove_noop() {
+        let programm = vec![1001, 9, 0, 9, 4, 9, 99, 0, 0, 5];
+        let optimized = optimize(&programm);
+
+        assert_eq!(vec![4, 5, 99, 0, 0, 5, 0, 0, 0, 0], optimized.programm);
+        assert_eq!(1, optimized.removed);
+        assert_eq!(0, optimized.map[0]);
+        assert_eq!(5, optimized.map[9]);
+        assert!(validate(&programm, &optimized.programm, &[vec![]]));
+    }
+
+    #[test]
+    fn test_jump_threading() {
+        let programm = vec![1105, 1, 6, 104, 1, 99, 1105, 1, 3];
+        let optimized = optimize(&programm);
+
+        assert_eq!(vec![104, 1, 99, 1105, 1, 0, 0, 0, 0], optimized.programm);
+        assert!(validate(&programm, &optimized.programm, &[vec![]]));
+    }
+
+    #[test]
+    fn test_relative_write() {
+        // the relative mode write at 0 turns the multiplication by zero at 4
+        // into a multiplication by 3
+        let programm = vec![
+            21101, 5, 0, 6, 1002, 20, 0, 20, 4, 20, 99, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3,
+        ];
+        let optimized = optimize(&programm);
+
+        assert_eq!(vec![15], run(&programm, &[]));
+        assert_eq!(programm, optimized.programm);
+        assert!(validate(&programm, &optimized.programm, &[vec![]]));
+    }
+
+    #[test]
+    fn test_conformance_programs() {
+        for case in crate::conformance::cases() {
+            let optimized = optimize(&case.programm);
+            let inputs = case.runs.iter().map(|r| r.input.clone()).collect::<Vec<_>>();
+
+            assert!(
+                validate(&case.programm, &optimized.programm, &inputs),
+                "{}",
+                case.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_boost() {
+        let raw = fs::read_to_string("input.txt").unwrap();
+        let programm = parse_programm(&raw);
+        let optimized = optimize(&programm);
+
+        assert!(validate(&programm, &optimized.programm, &[vec![1], vec![2]]));
+        // its stack lives past the code, so the constant checks get folded
+        assert!(optimized.rewritten > 0);
+    }
+}
@@ -49,6 +49,10 @@ impl Mem {
         }
     }
 
+    pub fn peek(&self, addr: usize) -> i64 {
+        self.raw.get(addr).copied().unwrap_or(0)
+    }
+
     pub fn get_opcodes(&mut self, addr: usize) -> &[i64] {
         if (addr + 3) > self.max_addr {
             self.raw.resize_with(addr + 4, Default::default);
@@ -96,7 +100,7 @@ enum State {
     Output,
 }
 
-fn parse_programm(input: &str) -> Vec<i64> {
+pub fn parse_programm(input: &str) -> Vec<i64> {
     input
         .lines()
         .next()
@@ -263,6 +267,30 @@ impl CPU {
         }
     }
 
+    pub fn step(&mut self) -> bool {
+        self.tick() != State::Halted
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn rb(&self) -> i64 {
+        self.rb
+    }
+
+    pub fn peek(&self, addr: usize) -> i64 {
+        self.mem.peek(addr)
+    }
+
+    pub fn memory(&self) -> &[i64] {
+        &self.mem.raw
+    }
+
+    pub fn waits_input(&self) -> bool {
+        self.mem.peek(self.ip) % 100 == 3 && self.input.is_empty()
+    }
+
     pub fn info(&self) -> CPUInfo {
         CPUInfo {
             ticks: self.ticks,
@@ -364,4 +392,13 @@ mod test {
         cpu.run();
         assert_eq!(code, cpu.output);
     }
+
+    #[test]
+    fn test_conformance() {
+        crate::conformance::check(&["day02", "day05", "day09"], |programm, input| {
+            let mut cpu = CPU::new(programm.to_vec(), input.to_vec());
+            cpu.run();
+            (cpu.output, cpu.mem.raw)
+        });
+    }
 }
@@ -0,0 +1,196 @@
+use crate::Asteroid;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+pub type Point = (i32, i32);
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// Direction from one point to another reduced by the gcd, two points are on
+// the same ray from the station exactly when their directions are equal.
+fn direction(from: Point, to: Point) -> Option<(Point, i32)> {
+    let dx = to.0 - from.0;
+    let dy = to.1 - from.1;
+
+    if dx == 0 && dy == 0 {
+        return None;
+    }
+
+    let steps = gcd(dx, dy);
+    Some(((dx / steps, dy / steps), steps))
+}
+
+pub struct Field {
+    points: Vec<Point>,
+    occupied: HashSet<Point>,
+}
+
+impl Field {
+    pub fn new(asteroids: &[Asteroid]) -> Self {
+        let points = asteroids.iter().map(|a| (a.x, a.y)).collect::<Vec<_>>();
+        let occupied = points.iter().copied().collect();
+
+        Self { points, occupied }
+    }
+
+    pub fn parse(input: &str) -> Self {
+        Self::new(&Asteroid::parse(input))
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        self.occupied.contains(&point)
+    }
+
+    pub fn visible_count(&self, station: Point) -> usize {
+        self.points
+            .iter()
+            .filter_map(|p| direction(station, *p))
+            .map(|(dir, _)| dir)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    // The closest asteroid in every direction.
+    pub fn visible(&self, station: Point) -> Vec<Point> {
+        let mut closest: HashMap<Point, (i32, Point)> = HashMap::new();
+
+        for point in self.points.iter() {
+            if let Some((dir, steps)) = direction(station, *point) {
+                let entry = closest.entry(dir).or_insert((steps, *point));
+                if steps < entry.0 {
+                    *entry = (steps, *point);
+                }
+            }
+        }
+
+        let mut result = closest.values().map(|(_, p)| *p).collect::<Vec<_>>();
+        result.sort();
+        result
+    }
+
+    // Whether nothing blocks the straight line between `a` and `b`, the end
+    // points themselves don't count.
+    pub fn line_of_sight(&self, a: Point, b: Point) -> bool {
+        match direction(a, b) {
+            Some(((dx, dy), steps)) => {
+                (1..steps).all(|i| !self.contains((a.0 + dx * i, a.1 + dy * i)))
+            }
+            None => true,
+        }
+    }
+
+    pub fn best_station(&self) -> Option<(usize, i32, i32)> {
+        self.points
+            .par_iter()
+            .map(|p| (self.visible_count(*p), p.0, p.1))
+            .max_by_key(|(count, _, _)| *count)
+    }
+
+    // Every asteroid in the order the laser hits it: rays are sorted once by
+    // their angle and each full turn takes the closest remaining asteroid of
+    // every ray.
+    pub fn vaporization_order(&self, station: Point) -> Vec<Point> {
+        let mut rays: HashMap<Point, Vec<(i32, Point)>> = HashMap::new();
+        for point in self.points.iter() {
+            if let Some((dir, steps)) = direction(station, *point) {
+                rays.entry(dir).or_default().push((steps, *point));
+            }
+        }
+
+        let origin = Asteroid::new(station.0, station.1);
+        let mut rays = rays
+            .into_iter()
+            .map(|(dir, mut points)| {
+                points.sort_unstable_by(|a, b| b.cmp(a));
+                let vector = origin.distance_to(&Asteroid::new(origin.x + dir.0, origin.y + dir.1));
+                (vector, points)
+            })
+            .collect::<Vec<_>>();
+        rays.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        let total = rays.iter().map(|(_, points)| points.len()).sum();
+        let mut result = Vec::with_capacity(total);
+        while result.len() < total {
+            for (_, points) in rays.iter_mut() {
+                if let Some((_, point)) = points.pop() {
+                    result.push(point);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{LaserIter, TaskA};
+
+    fn example() -> &'static str {
+        ".#..#\n.....\n#####\n....#\n...##\n"
+    }
+
+    #[test]
+    fn test_visible() {
+        let field = Field::parse(example());
+
+        assert_eq!(8, field.visible_count((3, 4)));
+        assert_eq!(7, field.visible_count((1, 0)));
+        assert_eq!(5, field.visible_count((4, 2)));
+        assert_eq!(8, field.visible((3, 4)).len());
+        assert!(!field.visible((3, 4)).contains(&(1, 0)));
+        assert_eq!(Some((8, 3, 4)), field.best_station());
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let field = Field::parse(example());
+
+        assert!(!field.line_of_sight((3, 4), (1, 0)));
+        assert!(field.line_of_sight((3, 4), (2, 2)));
+        assert!(field.line_of_sight((0, 2), (1, 2)));
+        assert!(!field.line_of_sight((0, 2), (4, 2)));
+        assert!(field.line_of_sight((0, 0), (0, 0)));
+    }
+
+    #[test]
+    fn test_vaporization_order() {
+        let input = ".#....#####...#..\n##...##.#####..##\n##...#...#.#####.\n..#.....X...###..\n..#.#.....#....##\n";
+        let field = Field::parse(input);
+        let order = field.vaporization_order((8, 3));
+
+        assert_eq!(field.len(), order.len());
+        assert_eq!(&[(8, 1), (9, 0), (9, 1), (10, 0), (9, 2)], &order[..5]);
+
+        let asteroids = Asteroid::parse(input);
+        let expected = LaserIter::new(&asteroids, 8, 3).collect::<Vec<_>>();
+        assert_eq!(expected, order);
+    }
+
+    #[test]
+    fn test_same_as_task_a() {
+        let input = "#.#.#...##\n.##..#.#..\n#..#.##.#.\n..###.#..#\n.#.##..###\n##...#.#.#\n";
+        let asteroids = Asteroid::parse(input);
+        let field = Field::new(&asteroids);
+
+        assert_eq!(
+            TaskA::new(&asteroids).solve().map(|r| r.0),
+            field.best_station().map(|r| r.0)
+        );
+    }
+}
@@ -1,6 +1,14 @@
+use field::Field;
+use num_rational::Ratio;
 use rayon::prelude::*;
+use replay::Replay;
+use std::env;
 use std::fs;
-use std::time::Instant;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+mod field;
+mod replay;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
@@ -55,12 +63,15 @@ impl Asteroid {
             _ => (dy.abs(), dx.abs()),
         };
 
-        let angel = (dx * 1_000_000) / (dy * 1_000);
+        // Tangent of the clockwise angle inside the quadrant. The divisor is
+        // never zero and the ratio is reduced, so two asteroids get equal
+        // vectors exactly when they are on the same ray.
+        let angel = Ratio::new(dx, dy);
         Some(Vector { quadrant, angel })
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 enum Quadrant {
     A,
     B,
@@ -68,10 +79,10 @@ enum Quadrant {
     D,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 struct Vector {
     quadrant: Quadrant,
-    angel: i32,
+    angel: Ratio<i32>,
 }
 
 struct TaskA<'a> {
@@ -191,30 +202,151 @@ impl Iterator for LaserIter {
     }
 }
 
-struct TaskB {
-    iter: LaserIter,
+// Square map with roughly `density` asteroids per thousand cells, the same
+// seed always gives the same map.
+fn generate(size: i32, density: u64, seed: u64) -> Vec<Asteroid> {
+    let mut state = seed;
+    let mut result = vec![];
+
+    for y in 0..size {
+        for x in 0..size {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            if (state >> 33) % 1000 < density {
+                result.push(Asteroid::new(x, y));
+            }
+        }
+    }
+
+    result
 }
 
-impl TaskB {
-    pub fn new(field: &[Asteroid], x: i32, y: i32) -> Self {
-        let iter = LaserIter::new(field, x, y);
-        Self { iter }
+fn bench(size: i32, density: u64) {
+    let asteroids = generate(size, density, 10);
+    println!("{}x{}, {} asteroids", size, size, asteroids.len());
+
+    let start = Instant::now();
+    let sorted = TaskA::new(&asteroids).solve();
+    println!("  best station, sort: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let field = Field::new(&asteroids);
+    let hashed = field.best_station();
+    println!("  best station, hash: {:?}", start.elapsed());
+
+    assert_eq!(sorted.map(|s| s.0), hashed.map(|s| s.0));
+    let (_, x, y) = match hashed {
+        Some(station) => station,
+        None => return,
+    };
+
+    let start = Instant::now();
+    let laser = LaserIter::new(&asteroids, x, y).collect::<Vec<_>>();
+    println!("  vaporization, scan: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let order = field.vaporization_order((x, y));
+    println!("  vaporization, rays: {:?}", start.elapsed());
+
+    assert_eq!(laser, order);
+}
+
+fn query(field: &Field, args: &[i32]) -> Result<()> {
+    match *args {
+        [x, y] => {
+            let visible = field.visible((x, y));
+            println!("{} visible from ({}, {})", visible.len(), x, y);
+            for (x, y) in visible {
+                println!("  {},{}", x, y);
+            }
+        }
+        [x1, y1, x2, y2] => {
+            let clear = field.line_of_sight((x1, y1), (x2, y2));
+            println!("line of sight: {}", if clear { "clear" } else { "blocked" });
+        }
+        _ => return Err("usage: day10 query <x> <y> [<x> <y>]".into()),
     }
 
-    pub fn solve(&mut self) -> Option<(i32, i32)> {
-        self.iter.nth(199)
+    Ok(())
+}
+
+fn export(replay: &Replay, dir: &Path) -> Result<()> {
+    let frames = dir.join("frames");
+    fs::create_dir_all(&frames)?;
+
+    fs::write(dir.join("replay.svg"), replay.animation_svg(0.1))?;
+
+    let mut text = String::new();
+    for shots in 0..=replay.len() {
+        let name = format!("frame-{:04}.svg", shots);
+        fs::write(frames.join(name), replay.frame_svg(shots))?;
+
+        text += &format!("shot {}\n", shots);
+        text += &replay.frame_text(shots).join("\n");
+        text += "\n\n";
     }
+    fs::write(dir.join("replay.txt"), text)?;
+
+    Ok(())
 }
 
 fn main() -> Result<()> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(String::as_str) == Some("bench") {
+        let size = args.get(1).map(|s| s.parse()).unwrap_or(Ok(1000))?;
+        let density = args.get(2).map(|s| s.parse()).unwrap_or(Ok(5))?;
+        bench(size, density);
+        return Ok(());
+    }
+
     let now = Instant::now();
 
     let input = fs::read_to_string("input.txt")?;
-    let field = Asteroid::parse(&input);
+    let field = Field::parse(&input);
+
+    if args.first().map(String::as_str) == Some("query") {
+        let coords = args[1..]
+            .iter()
+            .map(|s| s.parse())
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        return query(&field, &coords);
+    }
+
+    // day10 replay [<x> <y>] / day10 export [dir] [<x> <y>]
+    let command = args.first().map(String::as_str);
+    if command == Some("replay") || command == Some("export") {
+        let (dir, coords) = match command {
+            Some("export") if args.len() % 2 == 0 => (args[1].as_str(), &args[2..]),
+            _ => ("replay", &args[1..]),
+        };
+
+        let station = match coords {
+            [x, y] => (x.parse()?, y.parse()?),
+            [] => field
+                .best_station()
+                .map(|(_, x, y)| (x, y))
+                .ok_or("no asteroids")?,
+            _ => return Err("expected <x> <y> of the station".into()),
+        };
+
+        let replay = Replay::new(&field, station)?;
+        if command == Some("replay") {
+            replay::play(&replay, Duration::from_millis(50))?;
+        } else {
+            export(&replay, Path::new(dir))?;
+            println!("{} shots written to {}", replay.len(), dir);
+        }
+        return Ok(());
+    }
+
     let field_size = field.len();
 
-    let task_a = TaskA::new(&field).solve().unwrap();
-    let task_b = TaskB::new(&field, task_a.1, task_a.2).solve().unwrap();
+    let task_a = field.best_station().ok_or("no asteroids")?;
+    let task_b = *field
+        .vaporization_order((task_a.1, task_a.2))
+        .get(199)
+        .ok_or("less than 200 asteroids")?;
 
     let total_time = now.elapsed();
 
@@ -233,6 +365,9 @@ fn main() -> Result<()> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use proptest::prelude::*;
+    use std::cmp::Ordering;
+    use std::f64::consts::PI;
 
     impl Quadrant {
         fn from_char(input: char) -> Self {
@@ -251,7 +386,7 @@ mod test {
             let quadrant = Quadrant::from_char(quadrant);
             Self {
                 quadrant,
-                angel: (a * 1_000_000) / (b * 1_000),
+                angel: Ratio::new(a, b),
             }
         }
     }
@@ -305,4 +440,78 @@ mod test {
         assert_eq!(iter.next(), Some((1, 0)));
         assert_eq!(iter.next(), None);
     }
+
+    fn example_210() -> &'static str {
+        ".#..##.###...#######\n##.############..##.\n.#.######.########.#\n.###.#######.####.#.\n\
+         #####.##.#.##.###.##\n..#####..#.#########\n####################\n#.####....###.#.#.##\n\
+         ##.#################\n#####.##.###..####..\n..######..##.#######\n####.##.####...##..#\n\
+         .#####..#.######.###\n##...#.##########...\n#.##########.#######\n.####.#.###.###.#.##\n\
+         ....##.##.###..#####\n.#.#.###########.###\n#.#.#.#####.####.###\n###.##.####.##.#..##\n"
+    }
+
+    #[test]
+    fn test_example() {
+        let field = Asteroid::parse(example_210());
+        assert_eq!(Some((210, 11, 13)), TaskA::new(&field).solve());
+
+        let mut iter = LaserIter::new(&field, 11, 13);
+        assert_eq!(Some((11, 12)), iter.next());
+        assert_eq!(Some((12, 1)), iter.next());
+        assert_eq!(Some((8, 2)), iter.nth(197));
+    }
+
+    #[test]
+    fn test_large_map() {
+        // Far away directions that differ only in the 7th digit.
+        let origin = Asteroid::new(0, 0);
+        let a = origin.distance_to(&Asteroid::new(999_999, -1_000_000));
+        let b = origin.distance_to(&Asteroid::new(999_998, -999_999));
+        let c = origin.distance_to(&Asteroid::new(1_999_998, -2_000_000));
+
+        assert!(b < a);
+        assert_eq!(a, c);
+    }
+
+    // Clockwise angle from "up", y grows downwards.
+    fn reference(dx: i32, dy: i32) -> f64 {
+        let angle = (dx as f64).atan2(-dy as f64);
+        if angle < 0.0 {
+            angle + 2.0 * PI
+        } else {
+            angle
+        }
+    }
+
+    fn same_ray(a: (i32, i32), b: (i32, i32)) -> bool {
+        let (a, b) = ((a.0 as i64, a.1 as i64), (b.0 as i64, b.1 as i64));
+        a.0 * b.1 == a.1 * b.0 && a.0 * b.0 + a.1 * b.1 > 0
+    }
+
+    proptest! {
+        #[test]
+        fn prop_order_matches_atan2(
+            a in (-10_000..10_000, -10_000..10_000),
+            b in (-10_000..10_000, -10_000..10_000),
+        ) {
+            prop_assume!(a != (0, 0) && b != (0, 0));
+
+            let va = distance_to(0, 0, a.0, a.1).unwrap();
+            let vb = distance_to(0, 0, b.0, b.1).unwrap();
+
+            let expected = if same_ray(a, b) {
+                Ordering::Equal
+            } else {
+                reference(a.0, a.1).partial_cmp(&reference(b.0, b.1)).unwrap()
+            };
+
+            prop_assert_eq!(expected, va.cmp(&vb));
+        }
+
+        #[test]
+        fn prop_scaled_is_equal(x in -1000..1000, y in -1000..1000, k in 1..1000) {
+            prop_assume!((x, y) != (0, 0));
+
+            prop_assert_eq!(distance_to(5, 5, 5 + x, 5 + y), distance_to(5, 5, 5 + x * k, 5 + y * k));
+        }
+    }
 }
@@ -0,0 +1,510 @@
+use crate::field::{Field, Point};
+use crate::Result;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::{clear, color, cursor, style};
+
+// The shot the puzzle asks about, drawn differently everywhere.
+const AUDIT: usize = 200;
+const CELL: f64 = 16.0;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Cell {
+    Empty,
+    Asteroid,
+    Station,
+    Laser,
+    Vaporized(usize),
+}
+
+pub struct Replay {
+    width: i32,
+    height: i32,
+    station: Point,
+    asteroids: Vec<Point>,
+    order: Vec<Point>,
+}
+
+impl Replay {
+    // The station has to be one of the asteroids, which also keeps it on the
+    // map. It is drawn on its own, so it's not kept with the others.
+    pub fn new(field: &Field, station: Point) -> Result<Self> {
+        if !field.contains(station) {
+            return Err(format!("no asteroid at ({}, {})", station.0, station.1).into());
+        }
+
+        let points = field.points();
+        let width = points.iter().map(|p| p.0 + 1).max().unwrap_or(0);
+        let height = points.iter().map(|p| p.1 + 1).max().unwrap_or(0);
+        let asteroids = points
+            .iter()
+            .filter(|p| **p != station)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            width,
+            height,
+            station,
+            asteroids,
+            order: field.vaporization_order(station),
+        })
+    }
+
+    // Number of shots, the replay has one more frame than that.
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    // Clockwise angle from "up" in degrees, y grows downwards.
+    fn angle(&self, target: Point) -> f64 {
+        let dx = (target.0 - self.station.0) as f64;
+        let dy = (target.1 - self.station.1) as f64;
+        let angle = dx.atan2(-dy).to_degrees();
+        if angle < 0.0 {
+            angle + 360.0
+        } else {
+            angle
+        }
+    }
+
+    // Laser angle for every shot, unwrapped so the laser never turns back and
+    // every full turn adds 360 degrees.
+    fn sweep(&self) -> Vec<f64> {
+        let mut result: Vec<f64> = vec![];
+        for target in self.order.iter() {
+            let mut angle = self.angle(*target);
+            if let Some(last) = result.last() {
+                while angle <= *last {
+                    angle += 360.0;
+                }
+            }
+            result.push(angle);
+        }
+
+        result
+    }
+
+    fn cells(&self, shots: usize) -> Vec<Vec<Cell>> {
+        let mut result = vec![vec![Cell::Empty; self.width as usize]; self.height as usize];
+
+        for (x, y) in self.asteroids.iter() {
+            result[*y as usize][*x as usize] = Cell::Asteroid;
+        }
+
+        for (i, (x, y)) in self.order.iter().take(shots).enumerate() {
+            result[*y as usize][*x as usize] = Cell::Vaporized(i + 1);
+        }
+
+        if let Some(target) = shots.checked_sub(1).and_then(|i| self.order.get(i)) {
+            let dx = (target.0 - self.station.0) as f64;
+            let dy = (target.1 - self.station.1) as f64;
+            let steps = dx.abs().max(dy.abs());
+            let (sx, sy) = (dx / steps, dy / steps);
+
+            for i in 1.. {
+                let x = (self.station.0 as f64 + sx * i as f64).round() as i32;
+                let y = (self.station.1 as f64 + sy * i as f64).round() as i32;
+                if x < 0 || y < 0 || x >= self.width || y >= self.height {
+                    break;
+                }
+
+                let cell = &mut result[y as usize][x as usize];
+                if *cell == Cell::Empty {
+                    *cell = Cell::Laser;
+                }
+            }
+        }
+
+        let (x, y) = self.station;
+        result[y as usize][x as usize] = Cell::Station;
+        result
+    }
+
+    // The map after `shots` shots, vaporized asteroids are shown by the last
+    // digit of their number.
+    pub fn frame_text(&self, shots: usize) -> Vec<String> {
+        self.cells(shots)
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| match cell {
+                        Cell::Empty => '.',
+                        Cell::Asteroid => '#',
+                        Cell::Station => 'X',
+                        Cell::Laser => '*',
+                        Cell::Vaporized(n) => (b'0' + (n % 10) as u8) as char,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn status(&self, shots: usize) -> String {
+        let audit = match self.order.get(AUDIT - 1) {
+            Some((x, y)) => format!("#{}: {},{}", AUDIT, x, y),
+            None => format!("#{}: none", AUDIT),
+        };
+
+        match shots.checked_sub(1).and_then(|i| self.order.get(i)) {
+            Some((x, y)) => format!("shot {}/{}: {},{}   {}", shots, self.len(), x, y, audit),
+            None => format!("shot 0/{}   {}", self.len(), audit),
+        }
+    }
+
+    fn draw(&self, out: &mut impl Write, shots: usize) -> io::Result<()> {
+        for (y, row) in self.cells(shots).iter().enumerate() {
+            write!(out, "{}", cursor::Goto(1, y as u16 + 1))?;
+
+            for cell in row.iter() {
+                match cell {
+                    Cell::Empty => write!(out, "{}.", color::Fg(color::LightBlack))?,
+                    Cell::Asteroid => write!(out, "{}#", color::Fg(color::White))?,
+                    Cell::Station => write!(out, "{}X", color::Fg(color::LightGreen))?,
+                    Cell::Laser => write!(out, "{}*", color::Fg(color::Red))?,
+                    Cell::Vaporized(n) if *n == AUDIT => write!(
+                        out,
+                        "{}{}{}{}",
+                        style::Bold,
+                        color::Fg(color::LightCyan),
+                        n % 10,
+                        style::Reset
+                    )?,
+                    Cell::Vaporized(n) if *n == shots => {
+                        write!(out, "{}{}", color::Fg(color::LightYellow), n % 10)?
+                    }
+                    Cell::Vaporized(n) => write!(out, "{}{}", color::Fg(color::Yellow), n % 10)?,
+                }
+            }
+        }
+
+        write!(
+            out,
+            "{}{}{}{}",
+            cursor::Goto(1, self.height as u16 + 2),
+            color::Fg(color::Reset),
+            clear::CurrentLine,
+            self.status(shots)
+        )?;
+        write!(
+            out,
+            "{}space pause  ←→ step  q quit",
+            cursor::Goto(1, self.height as u16 + 3)
+        )?;
+
+        out.flush()
+    }
+
+    fn project(&self, (x, y): Point) -> (f64, f64) {
+        ((x as f64 + 0.5) * CELL, (y as f64 + 0.5) * CELL)
+    }
+
+    fn svg_header(&self) -> String {
+        let w = self.width as f64 * CELL;
+        let h = self.height as f64 * CELL + 2.0 * CELL;
+
+        format!(
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+                "\n",
+                r#"<rect width="{w}" height="{h}" fill="white"/>"#,
+                "\n"
+            ),
+            w = w,
+            h = h
+        )
+    }
+
+    fn svg_station(&self) -> String {
+        let (x, y) = self.project(self.station);
+        format!(
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{}\" height=\"{}\" fill=\"#2ca02c\"/>\n",
+            x - CELL / 3.0,
+            y - CELL / 3.0,
+            CELL / 1.5,
+            CELL / 1.5
+        )
+    }
+
+    fn svg_laser(&self, angle: f64, extra: &str) -> String {
+        let (x, y) = self.project(self.station);
+        let length = (self.width as f64).hypot(self.height as f64) * CELL;
+        format!(
+            "<line x1=\"{x:.1}\" y1=\"{y:.1}\" x2=\"{x:.1}\" y2=\"{end:.1}\" stroke=\"#d62728\" stroke-width=\"2\" transform=\"rotate({a:.2} {x:.1} {y:.1})\">{extra}</line>\n",
+            x = x,
+            y = y,
+            end = y - length,
+            a = angle,
+            extra = extra
+        )
+    }
+
+    fn svg_status(&self, text: &str) -> String {
+        format!(
+            "<text x=\"4\" y=\"{:.1}\" font-family=\"monospace\" font-size=\"12\">{}</text>\n",
+            self.height as f64 * CELL + CELL * 1.2,
+            text
+        )
+    }
+
+    // Number of the `n`th shot, with an animation it starts hidden and the
+    // animation reveals it.
+    fn svg_number(&self, n: usize, animation: Option<&str>) -> String {
+        let (x, y) = self.project(self.order[n - 1]);
+        let color = if n == AUDIT { "#1f77b4" } else { "#333" };
+        let hidden = if animation.is_some() {
+            " visibility=\"hidden\""
+        } else {
+            ""
+        };
+        format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"monospace\" font-size=\"{:.1}\" text-anchor=\"middle\" fill=\"{}\"{}>{}{}</text>\n",
+            x,
+            y + CELL / 8.0,
+            CELL / 2.5,
+            color,
+            hidden,
+            animation.unwrap_or(""),
+            n
+        )
+    }
+
+    // Static picture of the map after `shots` shots.
+    pub fn frame_svg(&self, shots: usize) -> String {
+        let shots = shots.min(self.len());
+        let vaporized = self
+            .order
+            .iter()
+            .take(shots)
+            .enumerate()
+            .map(|(i, p)| (*p, i + 1))
+            .collect::<HashMap<_, _>>();
+
+        let mut result = self.svg_header();
+
+        for p in self.asteroids.iter() {
+            let (x, y) = self.project(*p);
+            let fill = if vaporized.contains_key(p) {
+                "#ddd"
+            } else {
+                "#555"
+            };
+            writeln!(
+                result,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"{}\"/>",
+                x,
+                y,
+                CELL / 2.5,
+                fill
+            )
+            .unwrap();
+        }
+
+        for n in 1..=shots {
+            result += &self.svg_number(n, None);
+        }
+
+        if shots > 0 {
+            result += &self.svg_laser(self.sweep()[shots - 1], "");
+        }
+
+        result += &self.svg_station();
+        result += &self.svg_status(&self.status(shots));
+        result += "</svg>\n";
+
+        result
+    }
+
+    // Single SVG that replays the whole sweep with SMIL animations, every
+    // shot takes `seconds` and the laser turns smoothly between targets.
+    pub fn animation_svg(&self, seconds: f64) -> String {
+        let positions = self
+            .order
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (*p, i + 1))
+            .collect::<HashMap<_, _>>();
+
+        let mut result = self.svg_header();
+
+        for p in self.asteroids.iter() {
+            let (x, y) = self.project(*p);
+            let set = match positions.get(p) {
+                Some(n) => format!(
+                    "<set attributeName=\"fill\" to=\"#ddd\" begin=\"{:.2}s\" fill=\"freeze\"/>",
+                    *n as f64 * seconds
+                ),
+                None => String::new(),
+            };
+
+            writeln!(
+                result,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"#555\">{}</circle>",
+                x,
+                y,
+                CELL / 2.5,
+                set
+            )
+            .unwrap();
+        }
+
+        for n in 1..=self.len() {
+            let set = format!(
+                "<set attributeName=\"visibility\" to=\"visible\" begin=\"{:.2}s\" fill=\"freeze\"/>",
+                n as f64 * seconds
+            );
+            result += &self.svg_number(n, Some(&set));
+        }
+
+        if !self.order.is_empty() {
+            let (x, y) = self.project(self.station);
+            let angles = Some(0.0).into_iter().chain(self.sweep());
+            let values = angles
+                .map(|a| format!("{:.2} {:.1} {:.1}", a, x, y))
+                .collect::<Vec<_>>();
+            let times = (0..=self.len())
+                .map(|i| format!("{:.4}", i as f64 / self.len() as f64))
+                .collect::<Vec<_>>();
+
+            let animate = format!(
+                "<animateTransform attributeName=\"transform\" type=\"rotate\" values=\"{}\" keyTimes=\"{}\" dur=\"{:.2}s\" fill=\"freeze\"/>",
+                values.join(";"),
+                times.join(";"),
+                self.len() as f64 * seconds
+            );
+            result += &self.svg_laser(0.0, &animate);
+        }
+
+        result += &self.svg_station();
+        result += &self.svg_status(&self.status(self.len()));
+        result += "</svg>\n";
+
+        result
+    }
+}
+
+// Plays the replay in the terminal until the last shot, then waits for `q`.
+pub fn play(replay: &Replay, delay: Duration) -> io::Result<()> {
+    let mut stdout = io::stdout().into_raw_mode()?;
+    let mut keys = termion::async_stdin().keys();
+    let idle = Duration::from_millis(10);
+
+    write!(stdout, "{}{}", clear::All, cursor::Hide)?;
+
+    let mut shots = 0;
+    let mut running = true;
+    let mut waited = Duration::from_millis(0);
+
+    loop {
+        replay.draw(&mut stdout, shots)?;
+
+        match keys.next() {
+            Some(Ok(Key::Char('q'))) => break,
+            Some(Ok(Key::Char(' '))) => running = !running,
+            Some(Ok(Key::Right)) => {
+                running = false;
+                shots = (shots + 1).min(replay.len());
+            }
+            Some(Ok(Key::Left)) => {
+                running = false;
+                shots = shots.saturating_sub(1);
+            }
+            _ => {
+                thread::sleep(idle);
+                waited += idle;
+                if running && waited >= delay && shots < replay.len() {
+                    shots += 1;
+                    waited = Duration::from_millis(0);
+                }
+            }
+        }
+    }
+
+    write!(stdout, "{}{}", style::Reset, cursor::Show)?;
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn replay() -> Replay {
+        let input = ".#....#####...#..\n##...##.#####..##\n##...#...#.#####.\n..#.....#...###..\n..#.#.....#....##\n";
+        Replay::new(&Field::parse(input), (8, 3)).unwrap()
+    }
+
+    #[test]
+    fn test_frame_text() {
+        let replay = replay();
+
+        assert_eq!(36, replay.len());
+        assert_eq!(
+            vec![
+                ".#....#####...#..",
+                "##...##.#####..##",
+                "##...#...#.#####.",
+                "..#.....X...###..",
+                "..#.#.....#....##",
+            ],
+            replay.frame_text(0)
+        );
+
+        let frame = replay.frame_text(3);
+        assert_eq!(".#....###2#...#..", frame[0]);
+        assert_eq!("##...##.13###..##", frame[1]);
+        assert_eq!("##...#...#.#####.", frame[2]);
+
+        // the laser continues past the last target to the edge of the map
+        let frame = replay.frame_text(9);
+        assert_eq!("##...##.13#67.*9#", frame[1]);
+        assert_eq!("##...#...5*8####.", frame[2]);
+        assert_eq!("..#.....X*..###..", frame[3]);
+    }
+
+    #[test]
+    fn test_sweep() {
+        let field = Field::parse("#.#\n.#.\n");
+        let replay = Replay::new(&field, (1, 1)).unwrap();
+        assert_eq!(vec![45.0, 315.0], replay.sweep());
+
+        let field = Field::parse("#\n#\n#\n");
+        let replay = Replay::new(&field, (0, 2)).unwrap();
+        assert_eq!(vec![0.0, 360.0], replay.sweep());
+    }
+
+    #[test]
+    fn test_bad_station() {
+        let field = Field::parse("#.#\n.#.\n");
+
+        for station in [(1, 0), (5, 1), (-1, 0)].iter() {
+            assert!(Replay::new(&field, *station).is_err());
+        }
+    }
+
+    #[test]
+    fn test_svg() {
+        let replay = replay();
+
+        let frame = replay.frame_svg(5);
+        assert!(frame.starts_with("<svg"));
+        assert_eq!(36, frame.matches("<circle").count());
+        assert_eq!(5 + 1, frame.matches("<text").count());
+        assert_eq!(1, frame.matches("<line").count());
+        assert!(frame.contains("shot 5/36: 9,2"));
+        assert!(frame.trim_end().ends_with("</svg>"));
+
+        assert_eq!(0, replay.frame_svg(0).matches("<line").count());
+
+        let animation = replay.animation_svg(0.1);
+        assert_eq!(36, animation.matches("<set attributeName=\"fill\"").count());
+        assert_eq!(36, animation.matches("visibility=\"hidden\"").count());
+        assert_eq!(1, animation.matches("<animateTransform").count());
+        assert!(animation.contains("dur=\"3.60s\""));
+    }
+}
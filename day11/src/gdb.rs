@@ -0,0 +1,486 @@
+use crate::vm::CPU;
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+
+// gdb addresses bytes, every Intcode cell is shown as a little endian 64 bit
+// word. `ip` is reported as a byte address too, so `$pc` matches memory.
+const CELL: usize = 8;
+
+// Largest packet gdb may send or expect back, a memory read replies with two
+// hex digits per byte.
+const PACKET_SIZE: usize = 0x4000;
+
+// Byte addresses gdb may touch, memory grows up to here and no further.
+const MEMORY: usize = 1 << 24;
+
+const TARGET_XML: &str = concat!(
+    r#"<?xml version="1.0"?>"#,
+    r#"<!DOCTYPE target SYSTEM "gdb-target.dtd">"#,
+    r#"<target version="1.0">"#,
+    r#"<feature name="org.intcode.cpu">"#,
+    r#"<reg name="ip" bitsize="64" type="code_ptr" regnum="0"/>"#,
+    r#"<reg name="rb" bitsize="64" type="int64" regnum="1"/>"#,
+    r#"<reg name="ticks" bitsize="64" type="int64" regnum="2"/>"#,
+    r#"</feature>"#,
+    r#"</target>"#
+);
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(input: &str) -> Option<Vec<u8>> {
+    if !input.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..input.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(input.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn number(input: &str) -> Option<usize> {
+    usize::from_str_radix(input, 16).ok()
+}
+
+// Byte range `addr..addr + len` if it lies within `MEMORY`.
+fn range(addr: usize, len: usize) -> Option<std::ops::Range<usize>> {
+    addr.checked_add(len)
+        .filter(|end| *end <= MEMORY)
+        .map(|end| addr..end)
+}
+
+fn word(bytes: &[u8]) -> i64 {
+    let mut raw = [0; 8];
+    raw.copy_from_slice(&bytes[..8]);
+    i64::from_le_bytes(raw)
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0, |acc, b| acc.wrapping_add(b))
+}
+
+fn frame(data: &str) -> String {
+    format!("${}#{:02x}", data, checksum(data))
+}
+
+pub struct Stub<'c, 'a> {
+    cpu: &'c mut CPU<'a>,
+    breakpoints: HashSet<usize>,
+    halted: bool,
+    pub detached: bool,
+}
+
+impl<'c, 'a> Stub<'c, 'a> {
+    pub fn new(cpu: &'c mut CPU<'a>) -> Self {
+        Self {
+            cpu,
+            breakpoints: HashSet::new(),
+            halted: false,
+            detached: false,
+        }
+    }
+
+    // Returns the reply for a packet, an empty reply means "not supported".
+    // `None` is returned for a kill request, which has no reply at all.
+    pub fn handle(&mut self, packet: &str) -> Option<String> {
+        let (command, args) = packet.split_at(packet.len().min(1));
+
+        let reply = match command {
+            "?" => self.stop_reply(),
+            "g" => self.registers(),
+            "G" => self.write_registers(args),
+            "p" => number(args)
+                .and_then(|n| self.register(n))
+                .map(|value| hex(&value.to_le_bytes()))
+                .unwrap_or_else(|| "E01".to_owned()),
+            "P" => self.write_register(args),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "Z" | "z" => self.breakpoint(command == "Z", args),
+            "c" | "s" => self.resume(command == "s", args),
+            "H" | "T" => "OK".to_owned(),
+            "D" => {
+                self.detached = true;
+                "OK".to_owned()
+            }
+            "k" => {
+                self.detached = true;
+                return None;
+            }
+            "q" => self.query(packet),
+            _ => String::new(),
+        };
+
+        Some(reply)
+    }
+
+    fn stop_reply(&self) -> String {
+        if self.halted {
+            "W00".to_owned()
+        } else {
+            "S05".to_owned()
+        }
+    }
+
+    fn register(&self, n: usize) -> Option<i64> {
+        match n {
+            0 => Some((self.cpu.ip() * CELL) as i64),
+            1 => Some(self.cpu.rb()),
+            2 => Some(self.cpu.ticks() as i64),
+            _ => None,
+        }
+    }
+
+    fn valid_register(n: usize, value: i64) -> bool {
+        match n {
+            0 => value >= 0 && (value as usize) < MEMORY,
+            1 => true,
+            2 => value >= 0,
+            _ => false,
+        }
+    }
+
+    fn set_register(&mut self, n: usize, value: i64) -> bool {
+        if !Self::valid_register(n, value) {
+            return false;
+        }
+
+        match n {
+            0 => self.cpu.set_ip(value as usize / CELL),
+            1 => self.cpu.set_rb(value),
+            2 => self.cpu.set_ticks(value as usize),
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn registers(&self) -> String {
+        (0..3)
+            .filter_map(|n| self.register(n))
+            .map(|value| hex(&value.to_le_bytes()))
+            .collect()
+    }
+
+    fn write_registers(&mut self, args: &str) -> String {
+        match unhex(args) {
+            Some(ref bytes) if bytes.len() == 3 * 8 => {
+                let values = bytes.chunks(8).map(word).collect::<Vec<_>>();
+                if !values
+                    .iter()
+                    .enumerate()
+                    .all(|(n, value)| Self::valid_register(n, *value))
+                {
+                    return "E01".to_owned();
+                }
+
+                for (n, value) in values.into_iter().enumerate() {
+                    self.set_register(n, value);
+                }
+                "OK".to_owned()
+            }
+            _ => "E01".to_owned(),
+        }
+    }
+
+    fn write_register(&mut self, args: &str) -> String {
+        let mut parts = args.splitn(2, '=');
+        let n = parts.next().and_then(number);
+        let value = parts.next().and_then(unhex).filter(|v| v.len() == 8);
+
+        match (n, value) {
+            (Some(n), Some(value)) if self.set_register(n, word(&value)) => "OK".to_owned(),
+            _ => "E01".to_owned(),
+        }
+    }
+
+    fn byte(&self, addr: usize) -> u8 {
+        self.cpu.peek(addr / CELL).to_le_bytes()[addr % CELL]
+    }
+
+    fn set_byte(&mut self, addr: usize, value: u8) {
+        let mut bytes = self.cpu.peek(addr / CELL).to_le_bytes();
+        bytes[addr % CELL] = value;
+        self.cpu.poke(addr / CELL, i64::from_le_bytes(bytes));
+    }
+
+    fn read_memory(&self, args: &str) -> String {
+        let mut parts = args.splitn(2, ',');
+        let addr = parts.next().and_then(number);
+        let len = parts
+            .next()
+            .and_then(number)
+            .filter(|len| *len <= PACKET_SIZE / 2);
+
+        match (addr, len) {
+            (Some(addr), Some(len)) => match range(addr, len) {
+                Some(bytes) => hex(&bytes.map(|a| self.byte(a)).collect::<Vec<_>>()),
+                None => "E01".to_owned(),
+            },
+            _ => "E01".to_owned(),
+        }
+    }
+
+    fn write_memory(&mut self, args: &str) -> String {
+        let mut parts = args.splitn(2, ':');
+        let mut header = parts.next().unwrap_or("").splitn(2, ',');
+        let addr = header.next().and_then(number);
+        let len = header.next().and_then(number);
+        let data = parts.next().and_then(unhex);
+
+        match (addr, len, data) {
+            (Some(addr), Some(len), Some(data)) if data.len() == len => match range(addr, len) {
+                Some(bytes) => {
+                    for (a, value) in bytes.zip(data) {
+                        self.set_byte(a, value);
+                    }
+                    "OK".to_owned()
+                }
+                None => "E01".to_owned(),
+            },
+            _ => "E01".to_owned(),
+        }
+    }
+
+    fn breakpoint(&mut self, insert: bool, args: &str) -> String {
+        let mut parts = args.split(',');
+        let kind = parts.next();
+        let addr = parts.next().and_then(number);
+
+        match (kind, addr) {
+            (Some("0"), Some(addr)) | (Some("1"), Some(addr)) => {
+                if insert {
+                    self.breakpoints.insert(addr / CELL);
+                } else {
+                    self.breakpoints.remove(&(addr / CELL));
+                }
+                "OK".to_owned()
+            }
+            (Some(_), Some(_)) => String::new(),
+            _ => "E01".to_owned(),
+        }
+    }
+
+    fn resume(&mut self, single: bool, args: &str) -> String {
+        if let Some(addr) = number(args) {
+            if addr >= MEMORY {
+                return "E01".to_owned();
+            }
+            self.cpu.set_ip(addr / CELL);
+        }
+
+        while !self.halted {
+            if !self.cpu.step() {
+                self.halted = true;
+            } else if single || self.breakpoints.contains(&self.cpu.ip()) {
+                break;
+            }
+        }
+
+        self.stop_reply()
+    }
+
+    fn query(&self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return format!("PacketSize={:x};qXfer:features:read+", PACKET_SIZE);
+        }
+
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let mut parts = range.splitn(2, ',');
+            let offset = parts.next().and_then(number).unwrap_or(0);
+            let len = parts.next().and_then(number).unwrap_or(0);
+
+            let chunk = TARGET_XML.get(offset..).unwrap_or("");
+            return if chunk.len() > len {
+                format!("m{}", &chunk[..len])
+            } else {
+                format!("l{}", chunk)
+            };
+        }
+
+        match packet {
+            "qAttached" => "1".to_owned(),
+            "qC" => "QC1".to_owned(),
+            "qfThreadInfo" => "m1".to_owned(),
+            "qsThreadInfo" => "l".to_owned(),
+            _ => String::new(),
+        }
+    }
+}
+
+enum Packet {
+    Data(String),
+    Corrupted,
+}
+
+fn read_byte(reader: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut buf = [0];
+    match reader.read(&mut buf)? {
+        0 => Ok(None),
+        _ => Ok(Some(buf[0])),
+    }
+}
+
+// Acks and the break character (0x03) are skipped: the whole program runs
+// synchronously, so there is nothing to interrupt.
+fn read_packet(reader: &mut impl Read) -> io::Result<Option<Packet>> {
+    loop {
+        match read_byte(reader)? {
+            None => return Ok(None),
+            Some(b'$') => break,
+            Some(_) => continue,
+        }
+    }
+
+    let mut data = vec![];
+    loop {
+        match read_byte(reader)? {
+            None => return Ok(None),
+            Some(b'#') => break,
+            Some(b) => data.push(b),
+        }
+    }
+
+    let mut sum = [0; 2];
+    reader.read_exact(&mut sum)?;
+
+    let data = String::from_utf8_lossy(&data).into_owned();
+    let valid = std::str::from_utf8(&sum)
+        .ok()
+        .and_then(|s| u8::from_str_radix(s, 16).ok())
+        .is_some_and(|sum| sum == checksum(&data));
+
+    if valid {
+        Ok(Some(Packet::Data(data)))
+    } else {
+        Ok(Some(Packet::Corrupted))
+    }
+}
+
+pub fn serve(cpu: &mut CPU, mut reader: impl Read, mut writer: impl Write) -> io::Result<()> {
+    let mut stub = Stub::new(cpu);
+
+    while let Some(packet) = read_packet(&mut reader)? {
+        let data = match packet {
+            Packet::Data(data) => data,
+            Packet::Corrupted => {
+                writer.write_all(b"-")?;
+                writer.flush()?;
+                continue;
+            }
+        };
+
+        writer.write_all(b"+")?;
+        if let Some(reply) = stub.handle(&data) {
+            writer.write_all(frame(&reply).as_bytes())?;
+        }
+        writer.flush()?;
+
+        if stub.detached {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_frame() {
+        assert_eq!("$OK#9a", frame("OK"));
+        assert_eq!("$#00", frame(""));
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut cpu = CPU::new_from_str("109,5,99");
+        let mut stub = Stub::new(&mut cpu);
+
+        assert_eq!(Some("0".repeat(48)), stub.handle("g"));
+        assert_eq!(Some("S05".to_owned()), stub.handle("s"));
+        assert_eq!(Some("1000000000000000".to_owned()), stub.handle("p0"));
+        assert_eq!(Some("0500000000000000".to_owned()), stub.handle("p1"));
+        assert_eq!(Some("0100000000000000".to_owned()), stub.handle("p2"));
+        assert_eq!(Some("OK".to_owned()), stub.handle("P1=ffffffffffffffff"));
+        assert_eq!(-1, cpu.rb());
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut cpu = CPU::new_from_str("109,5,99");
+        let mut stub = Stub::new(&mut cpu);
+
+        assert_eq!(Some("6d00000000000000".to_owned()), stub.handle("m0,8"));
+        assert_eq!(Some("6300".to_owned()), stub.handle("m10,2"));
+        assert_eq!(Some("OK".to_owned()), stub.handle("M8,2:0701"));
+        assert_eq!(Some("E01".to_owned()), stub.handle("M8,2:07"));
+        assert_eq!(263, cpu.peek(1));
+    }
+
+    #[test]
+    fn test_bad_packets() {
+        let mut cpu = CPU::new_from_str("109,5,99");
+        let mut stub = Stub::new(&mut cpu);
+        let error = Some("E01".to_owned());
+
+        assert_eq!(error, stub.handle("mffffffffffffffff,2"));
+        assert_eq!(error, stub.handle("m0,2001"));
+        assert_eq!(Some(PACKET_SIZE), stub.handle("m0,2000").map(|r| r.len()));
+        assert_eq!(error, stub.handle("Mffffffffffffffff,1:00"));
+        assert_eq!(error, stub.handle("M7fffffffffff,1:00"));
+        assert_eq!(error, stub.handle("P0=ffffffffffffffff"));
+        assert_eq!(error, stub.handle("P2=ffffffffffffffff"));
+        assert_eq!(error, stub.handle(&format!("G{}", "ff".repeat(24))));
+        assert_eq!(error, stub.handle("cffffffffff"));
+
+        // nothing changed
+        assert_eq!(Some("0".repeat(48)), stub.handle("g"));
+        assert_eq!(109, cpu.peek(0));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let output = RefCell::new(vec![]);
+        let mut cpu = CPU::new_from_str("104,1,104,2,99");
+        cpu.output(|v| output.borrow_mut().push(v));
+        let mut stub = Stub::new(&mut cpu);
+
+        assert_eq!(Some("OK".to_owned()), stub.handle("Z0,10,1"));
+        assert_eq!(Some("S05".to_owned()), stub.handle("c"));
+        assert_eq!(vec![1], *output.borrow());
+        assert_eq!(Some("W00".to_owned()), stub.handle("c"));
+        assert_eq!(Some("W00".to_owned()), stub.handle("?"));
+        assert_eq!(vec![1, 2], *output.borrow());
+    }
+
+    #[test]
+    fn test_target_xml() {
+        let mut cpu = CPU::new_from_str("99");
+        let stub = Stub::new(&mut cpu);
+
+        let head = stub.query("qXfer:features:read:target.xml:0,10");
+        assert_eq!(r#"m<?xml version="1"#, head);
+
+        let all = stub.query("qXfer:features:read:target.xml:0,1000");
+        assert!(all.starts_with("l<?xml"));
+        assert!(all.contains(r#"name="ticks""#));
+    }
+
+    #[test]
+    fn test_serve() {
+        let mut cpu = CPU::new_from_str("109,5,99");
+        let input = format!("+{}{}$?#00{}", frame("qC"), frame("m0,1"), frame("k"));
+        let mut output = vec![];
+
+        serve(&mut cpu, input.as_bytes(), &mut output).unwrap();
+
+        let expected = format!("+{}+{}-+", frame("QC1"), frame("6d"));
+        assert_eq!(expected, String::from_utf8(output).unwrap());
+    }
+}
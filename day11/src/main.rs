@@ -1,9 +1,16 @@
+#[cfg(test)]
+#[path = "../../intcode/conformance.rs"]
+mod conformance;
+mod gdb;
 mod vm;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::env;
 use std::fmt;
 use std::fs;
 use std::hash::Hash;
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
 use vm::CPU;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
@@ -151,6 +158,13 @@ impl Game {
         cpu.run();
     }
 
+    pub fn debug(&mut self, reader: impl Read, writer: impl Write) -> io::Result<()> {
+        let mut cpu = CPU::new_from_str(&self.programm);
+        cpu.input(|| self.handle_input());
+        cpu.output(|v| self.handle_output(v));
+        gdb::serve(&mut cpu, reader, writer)
+    }
+
     pub fn total_painted(&self) -> usize {
         self.state.borrow().field.len()
     }
@@ -217,9 +231,33 @@ impl fmt::Display for Field {
     }
 }
 
+// `day11 gdb [addr]` waits for gdb on a TCP socket, `day11 gdb -` talks the
+// protocol over stdio (`target remote | day11 gdb -`).
+fn debug(input: &str, addr: Option<String>) -> Result<()> {
+    let mut game = Game::new(input, Color::Black);
+
+    match addr.as_deref() {
+        Some("-") => game.debug(io::stdin(), io::stdout())?,
+        addr => {
+            let listener = TcpListener::bind(addr.unwrap_or("127.0.0.1:1234"))?;
+            eprintln!("Waiting for gdb on {}", listener.local_addr()?);
+            let (stream, _) = listener.accept()?;
+            game.debug(stream.try_clone()?, stream)?;
+        }
+    }
+
+    eprintln!("Painted: {}", game.total_painted());
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let input = fs::read_to_string("input.txt")?;
 
+    let mut args = env::args().skip(1);
+    if args.next().as_deref() == Some("gdb") {
+        return debug(&input, args.next());
+    }
+
     let mut game_a = Game::new(&input, Color::Black);
     game_a.run();
     let task_a = game_a.total_painted();
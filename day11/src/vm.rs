@@ -49,6 +49,10 @@ impl Mem {
         }
     }
 
+    pub fn peek(&self, addr: usize) -> i64 {
+        self.raw.get(addr).copied().unwrap_or(0)
+    }
+
     pub fn get_opcodes(&mut self, addr: usize) -> &[i64] {
         if (addr + 3) > self.max_addr {
             self.raw.resize_with(addr + 4, Default::default);
@@ -265,6 +269,42 @@ impl<'a> CPU<'a> {
         self.output = Some(Box::new(f))
     }
 
+    pub fn step(&mut self) -> bool {
+        self.tick() != State::Halted
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn set_ip(&mut self, ip: usize) {
+        self.ip = ip;
+    }
+
+    pub fn rb(&self) -> i64 {
+        self.rb
+    }
+
+    pub fn set_rb(&mut self, rb: i64) {
+        self.rb = rb;
+    }
+
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+
+    pub fn set_ticks(&mut self, ticks: usize) {
+        self.ticks = ticks;
+    }
+
+    pub fn peek(&self, addr: usize) -> i64 {
+        self.mem.peek(addr)
+    }
+
+    pub fn poke(&mut self, addr: usize, value: i64) {
+        self.mem.set(addr, value);
+    }
+
     pub fn info(&self) -> CPUInfo {
         CPUInfo {
             ticks: self.ticks,
@@ -379,4 +419,20 @@ mod test {
 
         assert_eq!(code, output);
     }
+
+    #[test]
+    fn test_conformance() {
+        crate::conformance::check(&["day02", "day05", "day09"], |programm, input| {
+            let mut input = input.iter().copied();
+            let mut output = vec![];
+            let mut cpu = CPU::new(programm.to_vec());
+            cpu.input(move || input.next().expect("input is empty"));
+            cpu.output(|v| output.push(v));
+            cpu.run();
+            let memory = cpu.mem.raw.clone();
+            drop(cpu);
+
+            (output, memory)
+        });
+    }
 }
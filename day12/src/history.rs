@@ -0,0 +1,338 @@
+use crate::{axis_name, System};
+use serde_json::json;
+use std::fmt::Write;
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 300.0;
+const MARGIN: f64 = 40.0;
+const COLORS: [&str; 6] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b",
+];
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Snapshot {
+    pub pos: Vec<Vec<i32>>,
+    pub vel: Vec<Vec<i32>>,
+}
+
+impl Snapshot {
+    fn of(system: &System) -> Self {
+        Self {
+            pos: system.moons.iter().map(|m| m.pos.clone()).collect(),
+            vel: system.moons.iter().map(|m| m.vel.clone()).collect(),
+        }
+    }
+
+    pub fn energy(&self, moon: usize) -> i32 {
+        let pot = self.pos[moon].iter().map(|p| p.abs()).sum::<i32>();
+        let kin = self.vel[moon].iter().map(|v| v.abs()).sum::<i32>();
+        pot * kin
+    }
+
+    // Potential and kinetic parts of a single axis, summed over all moons.
+    pub fn axis_energy(&self, axis: usize) -> (i32, i32) {
+        let pot = self.pos.iter().map(|p| p[axis].abs()).sum();
+        let kin = self.vel.iter().map(|v| v[axis].abs()).sum();
+        (pot, kin)
+    }
+}
+
+// Every step of a simulation, starting with the initial state.
+pub struct History {
+    dim: usize,
+    steps: Vec<Snapshot>,
+}
+
+impl History {
+    pub fn record(system: &mut System, steps: usize) -> Self {
+        let mut result = vec![Snapshot::of(system)];
+
+        for _ in 0..steps {
+            system.step();
+            result.push(Snapshot::of(system));
+        }
+
+        Self {
+            dim: system.dim(),
+            steps: result,
+        }
+    }
+
+    pub fn to_csv(&self) -> String {
+        let axes = (0..self.dim).map(axis_name).collect::<Vec<_>>();
+        let mut header = vec!["step".to_owned(), "moon".to_owned()];
+        header.extend(axes.iter().map(|a| format!("p{}", a)));
+        header.extend(axes.iter().map(|a| format!("v{}", a)));
+        header.push("energy".to_owned());
+
+        let mut result = header.join(",");
+        result.push('\n');
+
+        for (step, snapshot) in self.steps.iter().enumerate() {
+            for moon in 0..snapshot.pos.len() {
+                let values = snapshot.pos[moon]
+                    .iter()
+                    .chain(snapshot.vel[moon].iter())
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>();
+
+                writeln!(
+                    result,
+                    "{},{},{},{}",
+                    step,
+                    moon,
+                    values.join(","),
+                    snapshot.energy(moon)
+                )
+                .unwrap();
+            }
+        }
+
+        result
+    }
+
+    pub fn to_json(&self) -> String {
+        let steps = self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(step, snapshot)| {
+                let moons = (0..snapshot.pos.len())
+                    .map(|moon| {
+                        json!({
+                            "pos": snapshot.pos[moon],
+                            "vel": snapshot.vel[moon],
+                            "energy": snapshot.energy(moon),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                let energy = (0..snapshot.pos.len())
+                    .map(|moon| snapshot.energy(moon))
+                    .sum::<i32>();
+
+                json!({ "step": step, "energy": energy, "moons": moons })
+            })
+            .collect::<Vec<_>>();
+
+        let axes = (0..self.dim).map(axis_name).collect::<Vec<_>>();
+        json!({ "axes": axes, "steps": steps }).to_string()
+    }
+
+    pub fn energy_plot(&self) -> String {
+        let total = self
+            .steps
+            .iter()
+            .map(|s| (0..s.pos.len()).map(|m| s.energy(m) as f64).sum())
+            .collect();
+
+        line_chart("total energy", &[("energy".to_owned(), total)])
+    }
+
+    pub fn axis_plot(&self, axis: usize) -> String {
+        let (pot, kin) = self
+            .steps
+            .iter()
+            .map(|s| s.axis_energy(axis))
+            .map(|(pot, kin)| (pot as f64, kin as f64))
+            .unzip();
+
+        let title = format!("axis {}", axis_name(axis));
+        line_chart(&title, &[("pot".to_owned(), pot), ("kin".to_owned(), kin)])
+    }
+
+    // Trajectories of all moons projected on the plane of axes `a` and `b`.
+    pub fn orbit_plot(&self, a: usize, b: usize) -> String {
+        let moons = self.steps.first().map(|s| s.pos.len()).unwrap_or(0);
+        let paths = (0..moons)
+            .map(|moon| {
+                self.steps
+                    .iter()
+                    .map(|s| (s.pos[moon][a] as f64, s.pos[moon][b] as f64))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let points = paths.iter().flatten();
+        let bounds = Bounds::of(points.clone().map(|p| p.0), points.map(|p| p.1));
+        let title = format!("orbits {}-{}", axis_name(a), axis_name(b));
+
+        let mut result = svg_header(&title);
+        for (i, path) in paths.iter().enumerate() {
+            let color = COLORS[i % COLORS.len()];
+            result += &polyline(path, &bounds, color);
+
+            if let Some((x, y)) = path.first() {
+                let (x, y) = bounds.project(*x, *y);
+                writeln!(
+                    result,
+                    r#"<circle cx="{:.1}" cy="{:.1}" r="3" fill="{}"/>"#,
+                    x, y, color
+                )
+                .unwrap();
+            }
+        }
+        result += "</svg>\n";
+
+        result
+    }
+}
+
+struct Bounds {
+    min_x: f64,
+    max_x: f64,
+    min_y: f64,
+    max_y: f64,
+}
+
+impl Bounds {
+    fn of(xs: impl Iterator<Item = f64>, ys: impl Iterator<Item = f64>) -> Self {
+        let (min_x, max_x) = xs.fold((0.0_f64, 0.0_f64), |(a, b), x| (a.min(x), b.max(x)));
+        let (min_y, max_y) = ys.fold((0.0_f64, 0.0_f64), |(a, b), y| (a.min(y), b.max(y)));
+
+        Self {
+            min_x,
+            max_x,
+            min_y,
+            max_y,
+        }
+    }
+
+    fn project(&self, x: f64, y: f64) -> (f64, f64) {
+        let w = (self.max_x - self.min_x).max(1.0);
+        let h = (self.max_y - self.min_y).max(1.0);
+
+        let x = MARGIN + (x - self.min_x) / w * (WIDTH - 2.0 * MARGIN);
+        let y = HEIGHT - MARGIN - (y - self.min_y) / h * (HEIGHT - 2.0 * MARGIN);
+        (x, y)
+    }
+}
+
+fn svg_header(title: &str) -> String {
+    format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            "\n",
+            r#"<rect width="{w}" height="{h}" fill="white"/>"#,
+            "\n",
+            r#"<text x="{m}" y="20" font-family="monospace" font-size="14">{title}</text>"#,
+            "\n"
+        ),
+        w = WIDTH,
+        h = HEIGHT,
+        m = MARGIN,
+        title = title
+    )
+}
+
+fn polyline(points: &[(f64, f64)], bounds: &Bounds, color: &str) -> String {
+    let points = points
+        .iter()
+        .map(|(x, y)| {
+            let (x, y) = bounds.project(*x, *y);
+            format!("{:.1},{:.1}", x, y)
+        })
+        .collect::<Vec<_>>();
+
+    format!(
+        "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1\" points=\"{}\"/>\n",
+        color,
+        points.join(" ")
+    )
+}
+
+fn line_chart(title: &str, series: &[(String, Vec<f64>)]) -> String {
+    let bounds = Bounds::of(
+        series
+            .iter()
+            .flat_map(|(_, s)| (0..s.len()).map(|x| x as f64)),
+        series.iter().flat_map(|(_, s)| s.iter().copied()),
+    );
+
+    let mut result = svg_header(title);
+
+    for (i, (name, values)) in series.iter().enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        let points = values
+            .iter()
+            .enumerate()
+            .map(|(x, y)| (x as f64, *y))
+            .collect::<Vec<_>>();
+
+        result += &polyline(&points, &bounds, color);
+        writeln!(
+            result,
+            r#"<text x="{:.1}" y="20" font-family="monospace" font-size="12" fill="{}">{}</text>"#,
+            WIDTH - MARGIN - 60.0 * (series.len() - i) as f64,
+            color,
+            name
+        )
+        .unwrap();
+    }
+    result += "</svg>\n";
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn history() -> History {
+        let input = "<x=-1, y=0, z=2>\n<x=2, y=-10, z=-7>\n<x=4, y=-8, z=8>\n<x=3, y=5, z=-1>";
+        let mut system = System::parse(input).unwrap();
+        History::record(&mut system, 2772)
+    }
+
+    #[test]
+    fn test_csv() {
+        let history = history();
+        let csv = history.to_csv();
+        let lines = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(2773, history.steps.len());
+        assert_eq!("step,moon,px,py,pz,vx,vy,vz,energy", lines[0]);
+        assert_eq!("0,0,-1,0,2,0,0,0,0", lines[1]);
+        assert_eq!("1,0,2,-1,1,3,-1,-1,20", lines[5]);
+        assert_eq!(1 + 4 * 2773, lines.len());
+    }
+
+    #[test]
+    fn test_json() {
+        let history = history();
+        let value: serde_json::Value = serde_json::from_str(&history.to_json()).unwrap();
+
+        assert_eq!(json!(["x", "y", "z"]), value["axes"]);
+        assert_eq!(json!([2, -1, 1]), value["steps"][1]["moons"][0]["pos"]);
+        assert_eq!(179, value["steps"][10]["energy"]);
+    }
+
+    #[test]
+    fn test_periodic() {
+        let history = history();
+
+        assert_eq!(history.steps[0], history.steps[2772]);
+        assert!(history.steps[1..2772]
+            .iter()
+            .all(|s| s != &history.steps[0]));
+    }
+
+    #[test]
+    fn test_svg() {
+        let history = history();
+
+        let plot = history.axis_plot(0);
+        assert!(plot.starts_with("<svg"));
+        assert!(plot.contains("axis x"));
+        assert_eq!(2, plot.matches("<polyline").count());
+
+        let plot = history.orbit_plot(0, 2);
+        assert!(plot.contains("orbits x-z"));
+        assert_eq!(4, plot.matches("<polyline").count());
+        assert_eq!(4, plot.matches("<circle").count());
+
+        let plot = history.energy_plot();
+        assert_eq!(1, plot.matches("<polyline").count());
+        assert!(plot.trim_end().ends_with("</svg>"));
+    }
+}
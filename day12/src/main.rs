@@ -1,61 +1,69 @@
+use history::History;
 use lazy_static::lazy_static;
 use num::Integer;
+use rayon::prelude::*;
 use regex::Regex;
+use std::env;
 use std::fmt;
 use std::fs;
-use std::ops::Add;
+use std::path::Path;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
-#[derive(Debug, PartialEq)]
-struct Moon {
-    px: i32,
-    py: i32,
-    pz: i32,
-    vx: i32,
-    vy: i32,
-    vz: i32,
+const AXES: [&str; 4] = ["x", "y", "z", "w"];
+
+fn axis_name(n: usize) -> String {
+    AXES.get(n)
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| format!("a{}", n))
 }
 
-fn parse_el(input: regex::Match) -> Option<i32> {
-    input.as_str().parse::<i32>().ok()
+#[derive(Debug, PartialEq)]
+struct Moon {
+    pos: Vec<i32>,
+    vel: Vec<i32>,
 }
 
 impl Moon {
-    pub fn new(px: i32, py: i32, pz: i32, vx: i32, vy: i32, vz: i32) -> Self {
-        Self {
-            px,
-            py,
-            pz,
-            vx,
-            vy,
-            vz,
-        }
+    pub fn new(pos: Vec<i32>, vel: Vec<i32>) -> Self {
+        assert_eq!(pos.len(), vel.len());
+        Self { pos, vel }
     }
 
+    // Accepts `<x=-1, y=0, z=2>`, any other names like `<a=1, b=2>` or just
+    // the numbers: `-1 0 2`, `-1, 0, 2`.
     pub fn parse(input: &str) -> Result<Self> {
         lazy_static! {
-            static ref RE: Regex = Regex::new(r"x=(-?\d+),\s*y=(-?\d+),\s*z=(-?\d+)").unwrap();
+            static ref RE: Regex = Regex::new(r"(?:[A-Za-z]\w*\s*=\s*)?(-?\d+)").unwrap();
         }
 
-        let caps = RE.captures(input).ok_or("invalid input")?;
-        let px = caps.get(1).and_then(parse_el).ok_or("invalid input")?;
-        let py = caps.get(2).and_then(parse_el).ok_or("invalid input")?;
-        let pz = caps.get(3).and_then(parse_el).ok_or("invalid input")?;
+        let pos = RE
+            .captures_iter(input)
+            .map(|caps| caps[1].parse::<i32>())
+            .collect::<std::result::Result<Vec<_>, _>>()?;
 
-        Ok(Moon::new(px, py, pz, 0, 0, 0))
+        if pos.is_empty() {
+            return Err("invalid input".into());
+        }
+
+        let vel = vec![0; pos.len()];
+        Ok(Moon::new(pos, vel))
+    }
+
+    pub fn dim(&self) -> usize {
+        self.pos.len()
     }
 
-    pub fn add_vel(&mut self, vel: &Vel) {
-        self.vx += vel.0;
-        self.vy += vel.1;
-        self.vz += vel.2;
+    pub fn add_vel(&mut self, vel: &[i32]) {
+        for (v, dv) in self.vel.iter_mut().zip(vel.iter()) {
+            *v += dv;
+        }
     }
 
     pub fn tick(&mut self) {
-        self.px += self.vx;
-        self.py += self.vy;
-        self.pz += self.vz;
+        for (p, v) in self.pos.iter_mut().zip(self.vel.iter()) {
+            *p += v;
+        }
     }
 
     pub fn energy(&self) -> i32 {
@@ -63,46 +71,37 @@ impl Moon {
     }
 
     fn pot(&self) -> i32 {
-        self.px.abs() + self.py.abs() + self.pz.abs()
+        self.pos.iter().map(|p| p.abs()).sum()
     }
 
     fn kin(&self) -> i32 {
-        self.vx.abs() + self.vy.abs() + self.vz.abs()
+        self.vel.iter().map(|v| v.abs()).sum()
     }
-}
 
-impl fmt::Display for Moon {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            fmt,
-            "pos=<x={:3}, y={:3}, z={:3}>, vel=<x={:3}, y={:3}, z={:3}>",
-            self.px, self.py, self.pz, self.vx, self.vy, self.vz
-        )
+    fn gravity(&self, other: &Moon) -> Vec<i32> {
+        self.pos
+            .iter()
+            .zip(other.pos.iter())
+            .map(|(a, b)| (b - a).signum())
+            .collect()
     }
 }
 
-#[derive(Debug)]
-struct Vel(i32, i32, i32);
-
-impl Vel {
-    fn for_moon(a: &Moon, b: &Moon) -> Self {
-        let dx = (b.px - a.px).signum();
-        let dy = (b.py - a.py).signum();
-        let dz = (b.pz - a.pz).signum();
-
-        Self(dx, dy, dz)
-    }
-
-    fn new() -> Self {
-        Self(0, 0, 0)
+fn format_vec(fmt: &mut fmt::Formatter, values: &[i32]) -> fmt::Result {
+    write!(fmt, "<")?;
+    for (i, value) in values.iter().enumerate() {
+        let sep = if i == 0 { "" } else { ", " };
+        write!(fmt, "{}{}={:3}", sep, axis_name(i), value)?;
     }
+    write!(fmt, ">")
 }
 
-impl Add for Vel {
-    type Output = Self;
-
-    fn add(self, other: Self) -> Self {
-        Self(self.0 + other.0, self.1 + other.1, self.2 + other.2)
+impl fmt::Display for Moon {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "pos=")?;
+        format_vec(fmt, &self.pos)?;
+        write!(fmt, ", vel=")?;
+        format_vec(fmt, &self.vel)
     }
 }
 
@@ -114,11 +113,23 @@ impl System {
     pub fn parse(input: &str) -> Result<Self> {
         let moons = input
             .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
             .map(|line| Moon::parse(line))
             .collect::<Result<Vec<_>>>()?;
+
+        let dim = moons.first().map(Moon::dim).ok_or("no moons")?;
+        if moons.iter().any(|moon| moon.dim() != dim) {
+            return Err("all moons must have the same number of axes".into());
+        }
+
         Ok(Self { moons })
     }
 
+    pub fn dim(&self) -> usize {
+        self.moons[0].dim()
+    }
+
     fn step(&mut self) {
         let vel = self
             .moons
@@ -126,8 +137,10 @@ impl System {
             .map(|i| {
                 self.moons
                     .iter()
-                    .map(move |j| Vel::for_moon(i, j))
-                    .fold(Vel::new(), |a, b| a + b)
+                    .map(move |j| i.gravity(j))
+                    .fold(vec![0; i.dim()], |a, b| {
+                        a.iter().zip(b.iter()).map(|(a, b)| a + b).collect()
+                    })
             })
             .collect::<Vec<_>>();
 
@@ -141,24 +154,15 @@ impl System {
         self.moons.iter().map(|m| m.energy()).sum()
     }
 
-    fn ax(&self) -> Vec<AxisMoon> {
+    fn axis(&self, n: usize) -> Vec<AxisMoon> {
         self.moons
             .iter()
-            .map(|moon| AxisMoon::new(moon.px, moon.vx))
+            .map(|moon| AxisMoon::new(moon.pos[n], moon.vel[n]))
             .collect()
     }
 
-    fn ay(&self) -> Vec<AxisMoon> {
-        self.moons
-            .iter()
-            .map(|moon| AxisMoon::new(moon.py, moon.vy))
-            .collect()
-    }
-    fn az(&self) -> Vec<AxisMoon> {
-        self.moons
-            .iter()
-            .map(|moon| AxisMoon::new(moon.pz, moon.vz))
-            .collect()
+    fn axes(&self) -> Vec<Vec<AxisMoon>> {
+        (0..self.dim()).map(|n| self.axis(n)).collect()
     }
 }
 
@@ -189,81 +193,165 @@ impl fmt::Display for AxisMoon {
     }
 }
 
-struct AxisSolver {
-    system: Vec<AxisMoon>,
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Cycle {
+    start: usize,
+    len: usize,
 }
 
-impl AxisSolver {
-    fn new(system: Vec<AxisMoon>) -> Self {
-        Self { system }
+impl Cycle {
+    pub fn new(start: usize, len: usize) -> Self {
+        Self { start, len }
     }
 
-    fn solve(&mut self) -> usize {
-        let mut i = 0;
-        let keep = self.system.clone();
+    // First step whose state was already seen before.
+    pub fn repeat_step(&self) -> usize {
+        self.start + self.len
+    }
 
-        for i in &self.system {
-            print!("{} ", i)
+    // Several systems evolving together only repeat once all of them are in
+    // their cycles and all the cycles are aligned.
+    pub fn combine(&self, other: &Cycle) -> Self {
+        Self {
+            start: self.start.max(other.start),
+            len: self.len.lcm(&other.len),
         }
+    }
+}
 
-        println!("");
+// Brent's cycle detection, keeps only two states in memory.
+fn find_cycle<S, F>(initial: &S, step: F) -> Cycle
+where
+    S: Clone + PartialEq,
+    F: Fn(&mut S),
+{
+    let mut power = 1;
+    let mut len = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = initial.clone();
+    step(&mut hare);
+
+    while tortoise != hare {
+        if power == len {
+            tortoise = hare.clone();
+            power *= 2;
+            len = 0;
+        }
+        step(&mut hare);
+        len += 1;
+    }
 
-        loop {
-            i += 1;
-            self.step();
+    let mut start = 0;
+    let mut tortoise = initial.clone();
+    let mut hare = initial.clone();
+    for _ in 0..len {
+        step(&mut hare);
+    }
 
-            if self.system == keep {
-                println!("Found on step {}", i);
-                return i;
-            }
-        }
+    while tortoise != hare {
+        step(&mut tortoise);
+        step(&mut hare);
+        start += 1;
     }
 
-    fn step(&mut self) {
-        let add: Vec<i32> = self
-            .system
+    Cycle::new(start, len)
+}
+
+struct AxisSolver {
+    system: Vec<AxisMoon>,
+}
+
+impl AxisSolver {
+    fn new(system: Vec<AxisMoon>) -> Self {
+        Self { system }
+    }
+
+    fn solve(&self) -> Cycle {
+        find_cycle(&self.system, |system| AxisSolver::step(system))
+    }
+
+    fn step(system: &mut [AxisMoon]) {
+        let add: Vec<i32> = system
             .iter()
-            .map(|i| self.system.iter().map(|j| (j.p - i.p).signum()).sum())
+            .map(|i| system.iter().map(|j| (j.p - i.p).signum()).sum())
             .collect();
 
-        for (a, b) in self.system.iter_mut().zip(add.iter()) {
+        for (a, b) in system.iter_mut().zip(add.iter()) {
             a.v += b
         }
 
-        for moon in self.system.iter_mut() {
+        for moon in system.iter_mut() {
             moon.p += moon.v
         }
     }
 }
 
 struct LoopSolver {
-    ax: AxisSolver,
-    ay: AxisSolver,
-    az: AxisSolver,
+    axes: Vec<AxisSolver>,
 }
 
 impl LoopSolver {
     pub fn new(system: &System) -> Self {
+        Self::from_axes(system.axes())
+    }
+
+    pub fn from_axes(axes: Vec<Vec<AxisMoon>>) -> Self {
         Self {
-            ax: AxisSolver::new(system.ax()),
-            ay: AxisSolver::new(system.ay()),
-            az: AxisSolver::new(system.az()),
+            axes: axes.into_iter().map(AxisSolver::new).collect(),
         }
     }
 
+    pub fn cycle(&self) -> Cycle {
+        self.axes
+            .par_iter()
+            .map(AxisSolver::solve)
+            .reduce(|| Cycle::new(0, 1), |a, b| a.combine(&b))
+    }
+
     pub fn solve(&mut self) -> usize {
-        let x = self.ax.solve();
-        let y = self.ay.solve();
-        let z = self.az.solve();
+        self.cycle().repeat_step()
+    }
+}
+
+fn export(system: &mut System, steps: usize, dir: &Path) -> Result<()> {
+    let history = History::record(system, steps);
+    fs::create_dir_all(dir)?;
+
+    fs::write(dir.join("history.csv"), history.to_csv())?;
+    fs::write(dir.join("history.json"), history.to_json())?;
+    fs::write(dir.join("energy.svg"), history.energy_plot())?;
 
-        dbg!(x, y, z);
-        x.lcm(&y).lcm(&z)
+    let dim = system.dim();
+    for a in 0..dim {
+        let name = format!("axis-{}.svg", axis_name(a));
+        fs::write(dir.join(name), history.axis_plot(a))?;
+
+        for b in (a + 1)..dim {
+            let name = format!("orbit-{}-{}.svg", axis_name(a), axis_name(b));
+            fs::write(dir.join(name), history.orbit_plot(a, b))?;
+        }
     }
+
+    Ok(())
 }
 
+mod history;
+
 fn main() -> Result<()> {
     let raw = fs::read_to_string("input.txt")?;
 
+    // day12 export <steps> [dir]
+    let args = env::args().collect::<Vec<_>>();
+    if args.get(1).map(String::as_str) == Some("export") {
+        let steps = args.get(2).ok_or("missing steps")?.parse::<usize>()?;
+        let dir = args.get(3).map(String::as_str).unwrap_or("history");
+
+        let mut system = System::parse(&raw)?;
+        export(&mut system, steps, Path::new(dir))?;
+        println!("{} steps written to {}", steps, dir);
+        return Ok(());
+    }
+
     let mut system = System::parse(&raw)?;
     for _ in 0..1000 {
         system.step();
@@ -286,15 +374,80 @@ mod test {
     fn test_parse_moon() {
         assert_eq!(
             Moon::parse("<x=-1, y=0, z=2>\n").unwrap(),
-            Moon::new(-1, 0, 2, 0, 0, 0)
+            Moon::new(vec![-1, 0, 2], vec![0, 0, 0])
         );
 
         assert_eq!(
             Moon::parse("<x=-100, y=99, z=2>\n").unwrap(),
-            Moon::new(-100, 99, 2, 0, 0, 0)
+            Moon::new(vec![-100, 99, 2], vec![0, 0, 0])
+        );
+    }
+
+    #[test]
+    fn test_parse_formats() {
+        assert_eq!(
+            Moon::parse("<a=1, b=-2>").unwrap(),
+            Moon::new(vec![1, -2], vec![0, 0])
+        );
+
+        assert_eq!(
+            Moon::parse("3, 4 -5 6").unwrap(),
+            Moon::new(vec![3, 4, -5, 6], vec![0, 0, 0, 0])
+        );
+
+        assert!(Moon::parse("<>").is_err());
+        assert!(System::parse("<x=1, y=2>\n<x=1, y=2, z=3>").is_err());
+    }
+
+    #[test]
+    fn test_energy() {
+        let input = r#"<x=-1, y=0, z=2>
+            <x=2, y=-10, z=-7>
+            <x=4, y=-8, z=8>
+            <x=3, y=5, z=-1>"#;
+
+        let mut system = System::parse(input).unwrap();
+        for _ in 0..10 {
+            system.step();
+        }
+        assert_eq!(179, system.energy());
+    }
+
+    #[test]
+    fn test_2d() {
+        let mut system = System::parse("<x=0, y=0>\n<x=2, y=1>").unwrap();
+        system.step();
+
+        assert_eq!(Moon::new(vec![1, 1], vec![1, 1]), system.moons[0]);
+        assert_eq!(Moon::new(vec![1, 0], vec![-1, -1]), system.moons[1]);
+        assert_eq!(6, system.energy());
+        assert_eq!(
+            "pos=<x=  1, y=  1>, vel=<x=  1, y=  1>",
+            format!("{}", system.moons[0])
         );
     }
 
+    #[test]
+    fn test_4d() {
+        let input = r#"<x=-1, y=0, z=2, w=0>
+            <x=2, y=-10, z=-7, w=0>
+            <x=4, y=-8, z=8, w=0>
+            <x=3, y=5, z=-1, w=0>"#;
+
+        let mut system = System::parse(input).unwrap();
+        assert_eq!(4, system.dim());
+        assert_eq!(2772, LoopSolver::new(&system).solve());
+
+        for _ in 0..10 {
+            system.step();
+        }
+        assert_eq!(179, system.energy());
+
+        let input = "-1 0 2 -1\n2 -10 -7 2\n4 -8 8 4\n3 5 -1 3";
+        let system = System::parse(input).unwrap();
+        assert_eq!(2772, LoopSolver::new(&system).solve());
+    }
+
     #[test]
     fn test_loop_sover() {
         let input = r#"<x=-1, y=0, z=2>
@@ -318,4 +471,45 @@ mod test {
         let result = LoopSolver::new(&system).solve();
         assert_eq!(4686774924, result);
     }
+
+    #[test]
+    fn test_find_cycle() {
+        // 0 1 2 3 4 5 6 | 7 8 9 10 11 | 7 ...
+        let cycle = find_cycle(&0, |n| *n = if *n == 11 { 7 } else { *n + 1 });
+        assert_eq!(Cycle::new(7, 5), cycle);
+        assert_eq!(12, cycle.repeat_step());
+
+        // 3 10 | 101 2 5 26 167 95 | 101 ...
+        let cycle = find_cycle(&3, |n| *n = (*n * *n + 1) % 255);
+        assert_eq!(Cycle::new(2, 6), cycle);
+
+        let cycle = find_cycle(&1, |_| {});
+        assert_eq!(Cycle::new(0, 1), cycle);
+    }
+
+    #[test]
+    fn test_combine_cycles() {
+        let cycle = Cycle::new(2, 4).combine(&Cycle::new(0, 6));
+        assert_eq!(Cycle::new(2, 12), cycle);
+        assert_eq!(14, cycle.repeat_step());
+    }
+
+    #[test]
+    fn test_loop_solver_axes() {
+        let axes = vec![
+            vec![AxisMoon::new(0, 0), AxisMoon::new(2, 0)],
+            vec![
+                AxisMoon::new(-1, 0),
+                AxisMoon::new(1, 0),
+                AxisMoon::new(5, 0),
+            ],
+        ];
+
+        let solver = LoopSolver::from_axes(axes.clone());
+        let x = LoopSolver::from_axes(vec![axes[0].clone()]).cycle();
+        let y = LoopSolver::from_axes(vec![axes[1].clone()]).cycle();
+
+        assert_eq!(0, x.start);
+        assert_eq!(x.combine(&y), solver.cycle());
+    }
 }
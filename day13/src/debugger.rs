@@ -0,0 +1,494 @@
+use crate::vm::{disassemble, CPU, IO};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, Write};
+use std::thread;
+use std::time;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::{color, cursor, style};
+
+const CODE_WIDTH: usize = 38;
+const CODE_HEIGHT: usize = 17;
+const SIDE_WIDTH: usize = 26;
+const MEMORY_COLUMNS: usize = 8;
+const MEMORY_ROWS: usize = 8;
+const LOG_SIZE: usize = 6;
+const RECENT_TICKS: usize = 32;
+const STEPS_PER_FRAME: usize = 500;
+
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Nothing,
+    Step,
+    // joystick value to queue for the next input instructions
+    Input(i64),
+    Quit,
+}
+
+// Values going through the VM. The queue is consumed before the puzzle's own
+// input function is asked.
+#[derive(Default)]
+pub struct Trace {
+    pub queue: RefCell<VecDeque<i64>>,
+    inputs: RefCell<Vec<i64>>,
+    outputs: RefCell<Vec<i64>>,
+}
+
+impl Trace {
+    fn input(&self, fallback: &mut impl FnMut() -> i64) -> i64 {
+        let value = self.queue.borrow_mut().pop_front();
+        let value = value.unwrap_or_else(fallback);
+        self.inputs.borrow_mut().push(value);
+        value
+    }
+
+    fn output(&self, value: i64) {
+        self.outputs.borrow_mut().push(value);
+    }
+}
+
+fn last(values: &[i64], n: usize) -> &[i64] {
+    &values[values.len().saturating_sub(n)..]
+}
+
+fn pad(line: String, width: usize) -> String {
+    let len = line.chars().count();
+    if len >= width {
+        line.chars().take(width).collect()
+    } else {
+        line + &" ".repeat(width - len)
+    }
+}
+
+// The debugger draws to the right of `left`, the columns before it stay free
+// for the puzzle's own visualization.
+pub struct Debugger {
+    left: u16,
+    breakpoints: HashSet<usize>,
+    cursor: Option<usize>,
+    memory_base: Option<usize>,
+    writes: HashMap<usize, usize>,
+    running: bool,
+    halted: bool,
+}
+
+impl Debugger {
+    pub fn new(left: u16) -> Self {
+        Self {
+            left,
+            breakpoints: HashSet::new(),
+            cursor: None,
+            memory_base: None,
+            writes: HashMap::new(),
+            running: false,
+            halted: false,
+        }
+    }
+
+    // Linear sweep from 0, an instruction that would swallow `ip` is shown as
+    // data so the current instruction is always on its own line.
+    fn listing(&self, cpu: &CPU) -> Vec<(usize, String)> {
+        let mem = cpu.memory();
+        let ip = cpu.ip();
+        let mut result = vec![];
+        let mut addr = 0;
+
+        while addr < mem.len() {
+            match disassemble(mem, addr) {
+                Some((text, size)) if !(addr < ip && ip < addr + size) => {
+                    result.push((addr, text));
+                    addr += size;
+                }
+                _ => {
+                    result.push((addr, format!(".data {}", mem[addr])));
+                    addr += 1;
+                }
+            }
+        }
+
+        result
+    }
+
+    fn selected(&self, cpu: &CPU) -> usize {
+        self.cursor.unwrap_or_else(|| cpu.ip())
+    }
+
+    pub fn code(&self, cpu: &CPU) -> Vec<String> {
+        let listing = self.listing(cpu);
+        let selected = self.selected(cpu);
+        let center = listing
+            .iter()
+            .position(|(addr, _)| *addr >= selected)
+            .unwrap_or(0);
+        let start = center
+            .saturating_sub(CODE_HEIGHT / 2)
+            .min(listing.len().saturating_sub(CODE_HEIGHT));
+
+        listing
+            .iter()
+            .skip(start)
+            .take(CODE_HEIGHT)
+            .map(|(addr, text)| {
+                let marker = if *addr == cpu.ip() { '>' } else { ' ' };
+                let bp = if self.breakpoints.contains(addr) {
+                    '●'
+                } else {
+                    ' '
+                };
+                let line = pad(format!("{}{}{:5} {}", bp, marker, addr, text), CODE_WIDTH);
+
+                if *addr == cpu.ip() {
+                    format!(
+                        "{}{}{}",
+                        color::Fg(color::Green),
+                        line,
+                        color::Fg(color::Reset)
+                    )
+                } else if *addr == selected {
+                    format!("{}{}{}", style::Invert, line, style::NoInvert)
+                } else {
+                    line
+                }
+            })
+            .collect()
+    }
+
+    pub fn side(&self, cpu: &CPU, trace: &Trace) -> Vec<String> {
+        let state = match (self.halted, self.running) {
+            (true, _) => "halted",
+            (false, true) => "running",
+            (false, false) => "paused",
+        };
+
+        let queue = trace
+            .queue
+            .borrow()
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>();
+        let inputs = trace.inputs.borrow();
+        let outputs = trace.outputs.borrow();
+
+        let mut lines = vec![
+            "── Registers".to_owned(),
+            format!("ip    {}", cpu.ip()),
+            format!("rb    {}", cpu.rb()),
+            format!("ticks {}", cpu.ticks()),
+            format!("state {}", state),
+            "── Input".to_owned(),
+            format!("queue [{}]", queue.join(", ")),
+            format!("last  {:?}", last(&inputs, 3)),
+            format!("── Output ({})", outputs.len()),
+        ];
+
+        lines.extend(last(&outputs, LOG_SIZE).iter().map(|v| v.to_string()));
+        lines.resize(9 + LOG_SIZE, String::new());
+
+        lines
+            .into_iter()
+            .map(|line| pad(line, SIDE_WIDTH))
+            .collect()
+    }
+
+    fn memory_start(&self, cpu: &CPU) -> usize {
+        let base = self
+            .memory_base
+            .or_else(|| cpu.last_write())
+            .or_else(|| self.writes.iter().max_by_key(|(_, t)| *t).map(|(a, _)| *a))
+            .unwrap_or(0);
+
+        (base / MEMORY_COLUMNS).saturating_sub(MEMORY_ROWS / 2) * MEMORY_COLUMNS
+    }
+
+    pub fn memory(&self, cpu: &CPU) -> Vec<String> {
+        let mem = cpu.memory();
+        let start = self.memory_start(cpu);
+
+        (0..MEMORY_ROWS)
+            .map(|row| {
+                let addr = start + row * MEMORY_COLUMNS;
+                let cells = (addr..addr + MEMORY_COLUMNS)
+                    .map(|a| {
+                        let value = mem.get(a).copied().unwrap_or(0);
+                        let text = if value < 0 {
+                            format!("{:>7}", format!("-{:x}", -(value as i128)))
+                        } else {
+                            format!("{:>7x}", value)
+                        };
+
+                        match self.writes.get(&a) {
+                            Some(t) if cpu.ticks() - t < RECENT_TICKS => format!(
+                                "{}{}{}",
+                                color::Fg(color::Yellow),
+                                text,
+                                color::Fg(color::Reset)
+                            ),
+                            _ => text,
+                        }
+                    })
+                    .collect::<String>();
+
+                format!("{:5x}:{}", addr, cells)
+            })
+            .collect()
+    }
+
+    pub fn toggle_breakpoint(&mut self, addr: usize) {
+        if !self.breakpoints.remove(&addr) {
+            self.breakpoints.insert(addr);
+        }
+    }
+
+    fn move_cursor(&mut self, cpu: &CPU, delta: isize) {
+        let listing = self.listing(cpu);
+        let selected = self.selected(cpu);
+        let current = listing
+            .iter()
+            .position(|(addr, _)| *addr >= selected)
+            .unwrap_or(0) as isize;
+        let next = (current + delta).max(0).min(listing.len() as isize - 1);
+
+        self.cursor = listing.get(next as usize).map(|(addr, _)| *addr);
+    }
+
+    pub fn on_key(&mut self, key: Key, cpu: &CPU) -> Action {
+        match key {
+            Key::Char('q') | Key::Ctrl('c') => return Action::Quit,
+            Key::Char('s') | Key::Char(' ') if !self.halted => {
+                self.running = false;
+                return Action::Step;
+            }
+            Key::Char('c') if !self.halted => self.running = true,
+            Key::Char('p') => self.running = false,
+            Key::Char('b') => self.toggle_breakpoint(self.selected(cpu)),
+            Key::Char('f') => {
+                self.cursor = None;
+                self.memory_base = None;
+            }
+            Key::Left => return Action::Input(-1),
+            Key::Right => return Action::Input(1),
+            Key::Char('n') => return Action::Input(0),
+            Key::Up => self.move_cursor(cpu, -1),
+            Key::Down => self.move_cursor(cpu, 1),
+            Key::PageUp => {
+                let start = self.memory_start(cpu);
+                let base = start.saturating_sub(MEMORY_COLUMNS * MEMORY_ROWS / 2);
+                self.memory_base = Some(base);
+            }
+            Key::PageDown => {
+                let start = self.memory_start(cpu);
+                let base = start + MEMORY_COLUMNS * MEMORY_ROWS * 3 / 2;
+                self.memory_base = Some(base);
+            }
+            _ => {}
+        }
+
+        Action::Nothing
+    }
+
+    pub fn step(&mut self, cpu: &mut CPU, io: &mut IO) {
+        if self.halted {
+            return;
+        }
+
+        self.halted = !cpu.step(io);
+
+        if let Some(addr) = cpu.last_write() {
+            self.writes.insert(addr, cpu.ticks());
+        }
+
+        if self.halted {
+            self.running = false;
+        }
+    }
+
+    // Runs until a breakpoint, the halt or the end of the step budget.
+    pub fn advance(&mut self, cpu: &mut CPU, io: &mut IO, steps: usize) {
+        for _ in 0..steps {
+            self.step(cpu, io);
+
+            if self.halted || self.breakpoints.contains(&cpu.ip()) {
+                self.running = false;
+                break;
+            }
+        }
+    }
+
+    fn draw(&self, out: &mut impl Write, cpu: &CPU, trace: &Trace) -> io::Result<()> {
+        let left = self.left;
+        let side = left + CODE_WIDTH as u16 + 2;
+
+        write!(out, "{}{}── Code", cursor::Goto(left, 1), cursor::Hide)?;
+        for (i, line) in self.code(cpu).iter().enumerate() {
+            write!(out, "{}{}", cursor::Goto(left, i as u16 + 2), line)?;
+        }
+
+        for (i, line) in self.side(cpu, trace).iter().enumerate() {
+            write!(out, "{}{}", cursor::Goto(side, i as u16 + 1), line)?;
+        }
+
+        let top = CODE_HEIGHT as u16 + 3;
+        write!(out, "{}── Memory", cursor::Goto(left, top))?;
+        for (i, line) in self.memory(cpu).iter().enumerate() {
+            write!(out, "{}{}", cursor::Goto(left, top + i as u16 + 1), line)?;
+        }
+
+        let help = "s step  c continue  p pause  b breakpoint  ↑↓ select  PgUp/PgDn memory  f follow  ←n→ joystick  q quit";
+        write!(
+            out,
+            "{}{}",
+            cursor::Goto(left, top + MEMORY_ROWS as u16 + 2),
+            help
+        )?;
+
+        out.flush()
+    }
+}
+
+pub fn run(
+    cpu: &mut CPU,
+    mut input: impl FnMut() -> i64,
+    mut output: impl FnMut(i64),
+    left: u16,
+) -> io::Result<()> {
+    let trace = Trace::default();
+    let mut io = IO::new(
+        || trace.input(&mut input),
+        |value| {
+            trace.output(value);
+            output(value);
+        },
+    );
+
+    let mut debugger = Debugger::new(left);
+    let mut stdout = io::stdout().into_raw_mode()?;
+    let mut keys = termion::async_stdin().keys();
+    let idle = time::Duration::from_millis(10);
+
+    loop {
+        debugger.draw(&mut stdout, cpu, &trace)?;
+
+        let key = keys.next();
+        let action = match key {
+            Some(Ok(key)) => debugger.on_key(key, cpu),
+            _ => Action::Nothing,
+        };
+
+        match action {
+            Action::Quit => break,
+            Action::Step => debugger.step(cpu, &mut io),
+            Action::Input(value) => trace.queue.borrow_mut().push_back(value),
+            Action::Nothing if debugger.running => debugger.advance(cpu, &mut io, STEPS_PER_FRAME),
+            Action::Nothing if key.is_none() => thread::sleep(idle),
+            Action::Nothing => {}
+        }
+    }
+
+    write!(stdout, "{}", cursor::Show)?;
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn strip(line: &str) -> String {
+        let mut result = String::new();
+        let mut escape = false;
+
+        for c in line.chars() {
+            match c {
+                '\x1b' => escape = true,
+                c if escape && c.is_ascii_alphabetic() => escape = false,
+                _ if escape => {}
+                c => result.push(c),
+            }
+        }
+
+        result
+    }
+
+    #[test]
+    fn test_code() {
+        let mut cpu = CPU::new_from_str("1101,2,3,9,104,5,99");
+        let mut debugger = Debugger::new(1);
+
+        let lines = debugger.code(&cpu);
+        assert_eq!(" >    0 add #2, #3 -> [9]", strip(&lines[0]).trim_end());
+        assert_eq!("      4 out #5", strip(&lines[1]).trim_end());
+
+        debugger.on_key(Key::Down, &cpu);
+        debugger.on_key(Key::Char('b'), &cpu);
+        let mut io = IO::output(|_| {});
+        debugger.step(&mut cpu, &mut io);
+
+        let lines = debugger.code(&cpu);
+        assert_eq!("●>    4 out #5", strip(&lines[1]).trim_end());
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut output = vec![];
+        let mut cpu = CPU::new_from_str("104,1,104,2,104,3,99");
+        let mut debugger = Debugger::new(1);
+        debugger.toggle_breakpoint(4);
+
+        let mut io = IO::output(|v| output.push(v));
+        debugger.advance(&mut cpu, &mut io, 100);
+        assert_eq!(4, cpu.ip());
+
+        debugger.advance(&mut cpu, &mut io, 100);
+        assert!(debugger.halted);
+        drop(io);
+
+        assert_eq!(vec![1, 2, 3], output);
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut cpu = CPU::new_from_str("1101,-2,-3,9,99");
+        let mut debugger = Debugger::new(1);
+        let mut io = IO::fail();
+        debugger.step(&mut cpu, &mut io);
+
+        let lines = debugger.memory(&cpu);
+        let yellow = color::Fg(color::Yellow).to_string();
+
+        assert!(lines[1].contains(&format!("{}     -5", yellow)));
+        assert_eq!(
+            "    8:      0     -5      0      0      0      0      0      0",
+            strip(&lines[1])
+        );
+    }
+
+    #[test]
+    fn test_joystick() {
+        let cpu = CPU::new_from_str("3,9,99");
+        let mut debugger = Debugger::new(1);
+
+        assert_eq!(Action::Input(-1), debugger.on_key(Key::Left, &cpu));
+        assert_eq!(Action::Input(0), debugger.on_key(Key::Char('n'), &cpu));
+        assert_eq!(Action::Input(1), debugger.on_key(Key::Right, &cpu));
+    }
+
+    #[test]
+    fn test_side() {
+        let trace = Trace::default();
+        trace.queue.borrow_mut().push_back(7);
+        let mut fallback = || 1;
+
+        let mut cpu = CPU::new_from_str("3,9,4,9,99");
+        let mut debugger = Debugger::new(1);
+        let mut io = IO::new(|| trace.input(&mut fallback), |v| trace.output(v));
+        debugger.advance(&mut cpu, &mut io, 100);
+        drop(io);
+
+        let lines = debugger.side(&cpu, &trace);
+        assert_eq!("state halted", lines[4].trim_end());
+        assert_eq!("queue []", lines[6].trim_end());
+        assert_eq!("last  [7]", lines[7].trim_end());
+        assert_eq!("7", lines[9].trim_end());
+    }
+}
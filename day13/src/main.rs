@@ -1,6 +1,7 @@
 use crate::vm::{CPU, IO};
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::env;
 use std::fmt;
 use std::fs;
 use std::hash::Hash;
@@ -113,13 +114,15 @@ enum Output {
 struct Game {
     input: String,
     field: Field,
+    debug: bool,
 }
 
 impl Game {
-    pub fn new(input: &str) -> Self {
+    pub fn new(input: &str, debug: bool) -> Self {
         Self {
             field: Field::new(),
             input: input.to_owned(),
+            debug,
         }
     }
 
@@ -158,8 +161,11 @@ impl Game {
 
         let sleep_interval = time::Duration::from_millis(40);
 
+        let debug = self.debug;
         let input = || {
-            thread::sleep(sleep_interval);
+            if !debug {
+                thread::sleep(sleep_interval);
+            }
             self.field.predict()
         };
 
@@ -172,9 +178,11 @@ impl Game {
             None => {}
         };
 
-        let io = IO::new(input, output);
-
-        cpu.run(io);
+        if self.debug {
+            debugger::run(&mut cpu, input, output, 60).expect("debugger failed");
+        } else {
+            cpu.run(IO::new(input, output));
+        }
 
         0
     }
@@ -234,13 +242,18 @@ fn predict_by_coord(ball: Coord, paddle: Coord) -> i64 {
     }
 }
 
+#[cfg(test)]
+#[path = "../../intcode/conformance.rs"]
+mod conformance;
+mod debugger;
 mod vm;
 
 fn main() {
     println!("{}", clear::All);
 
     let input = fs::read_to_string("input.txt").expect("cant' read input.txt");
-    let mut game = Game::new(&input);
+    let debug = env::args().nth(1).as_deref() == Some("debug");
+    let mut game = Game::new(&input, debug);
 
     let task_1 = game.block_num();
     let task_2 = game.final_score();
@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Debug, PartialEq)]
 enum Mode {
     Position,
@@ -14,6 +16,15 @@ impl Mode {
             n => panic!("invalid mode: {}", n),
         }
     }
+
+    fn format(&self, value: i64) -> String {
+        match self {
+            Mode::Position => format!("[{}]", value),
+            Mode::Immediate => format!("#{}", value),
+            Mode::Relative if value < 0 => format!("[rb{}]", value),
+            Mode::Relative => format!("[rb+{}]", value),
+        }
+    }
 }
 
 type ModeSet = (Mode, Mode, Mode);
@@ -22,6 +33,7 @@ type ModeSet = (Mode, Mode, Mode);
 struct Mem {
     raw: Vec<i64>,
     max_addr: usize,
+    last_write: Option<usize>,
 }
 
 impl Mem {
@@ -31,6 +43,7 @@ impl Mem {
             self.max_addr = addr;
         }
         self.raw[addr] = value;
+        self.last_write = Some(addr);
     }
 
     pub fn get(&mut self, addr: usize) -> i64 {
@@ -47,6 +60,7 @@ impl Mem {
         Self {
             raw: programm,
             max_addr,
+            last_write: None,
         }
     }
 
@@ -89,6 +103,49 @@ impl Command {
     }
 }
 
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Command::*;
+
+        match self {
+            Halt => write!(f, "hlt"),
+            Input(a, m) => write!(f, "in -> {}", m.0.format(*a)),
+            Output(a, m) => write!(f, "out {}", m.0.format(*a)),
+            Add(a, b, c, m) => write!(
+                f,
+                "add {}, {} -> {}",
+                m.0.format(*a),
+                m.1.format(*b),
+                m.2.format(*c)
+            ),
+            Mul(a, b, c, m) => write!(
+                f,
+                "mul {}, {} -> {}",
+                m.0.format(*a),
+                m.1.format(*b),
+                m.2.format(*c)
+            ),
+            JumpTrue(a, b, m) => write!(f, "jt {}, {}", m.0.format(*a), m.1.format(*b)),
+            JumpFalse(a, b, m) => write!(f, "jf {}, {}", m.0.format(*a), m.1.format(*b)),
+            LessThan(a, b, c, m) => write!(
+                f,
+                "lt {}, {} -> {}",
+                m.0.format(*a),
+                m.1.format(*b),
+                m.2.format(*c)
+            ),
+            Equals(a, b, c, m) => write!(
+                f,
+                "eq {}, {} -> {}",
+                m.0.format(*a),
+                m.1.format(*b),
+                m.2.format(*c)
+            ),
+            UpdateRelative(a, m) => write!(f, "arb {}", m.0.format(*a)),
+        }
+    }
+}
+
 #[derive(PartialEq, Debug)]
 enum State {
     Running,
@@ -185,6 +242,7 @@ impl CPU {
 
     fn tick(&mut self, io: &mut IO) -> State {
         let original_ip = self.ip;
+        self.mem.last_write = None;
 
         let command = decode(self.mem.get_opcodes(self.ip));
         self.process(&command, io);
@@ -295,6 +353,30 @@ impl CPU {
         self.mem.set(addr, value);
     }
 
+    pub fn step(&mut self, io: &mut IO) -> bool {
+        self.tick(io) != State::Halted
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn rb(&self) -> i64 {
+        self.rb
+    }
+
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+
+    pub fn memory(&self) -> &[i64] {
+        &self.mem.raw
+    }
+
+    pub fn last_write(&self) -> Option<usize> {
+        self.mem.last_write
+    }
+
     #[allow(dead_code)]
     pub fn info(&self) -> CPUInfo {
         CPUInfo {
@@ -316,6 +398,25 @@ fn decode_opcode(input: i64) -> (i64, ModeSet) {
     )
 }
 
+fn is_valid(code: i64) -> bool {
+    let modes = [code / 100 % 10, code / 1_000 % 10, code / 10_000 % 10];
+    code >= 0 && matches!(code % 100, 1..=9 | 99) && modes.iter().all(|m| *m <= 2)
+}
+
+// Text and size of the instruction at `addr`, `None` if it doesn't decode.
+pub fn disassemble(mem: &[i64], addr: usize) -> Option<(String, usize)> {
+    let code = *mem.get(addr)?;
+    if !is_valid(code) {
+        return None;
+    }
+
+    let mut cells = mem[addr..mem.len().min(addr + 4)].to_vec();
+    cells.resize(4, 0);
+
+    let command = decode(&cells);
+    Some((command.to_string(), command.size()))
+}
+
 fn decode(mem: &[i64]) -> Command {
     let (opcode, modeset) = decode_opcode(mem[0]);
     match opcode {
@@ -407,4 +508,34 @@ mod test {
 
         assert_eq!(code, output);
     }
+
+    #[test]
+    fn test_conformance() {
+        crate::conformance::check(&["day02", "day05", "day09"], |programm, input| {
+            let mut input = input.iter().copied();
+            let mut output = vec![];
+            let io = IO::new(
+                move || input.next().expect("input is empty"),
+                |v| output.push(v),
+            );
+            let mut cpu = CPU::new(programm.to_vec());
+            cpu.run(io);
+
+            (output, cpu.mem.raw)
+        });
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let mem = vec![21101, 3, 4, -1, 204, 2, 1006, 7, 0, 99, 42];
+
+        assert_eq!(
+            Some(("add #3, #4 -> [rb-1]".to_owned(), 4)),
+            disassemble(&mem, 0)
+        );
+        assert_eq!(Some(("out [rb+2]".to_owned(), 2)), disassemble(&mem, 4));
+        assert_eq!(Some(("jf [7], #0".to_owned(), 3)), disassemble(&mem, 6));
+        assert_eq!(Some(("hlt".to_owned(), 1)), disassemble(&mem, 9));
+        assert_eq!(None, disassemble(&mem, 10));
+    }
 }
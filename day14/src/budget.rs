@@ -0,0 +1,216 @@
+use crate::{Choice, Lab, Plan, Result, Stock};
+use std::collections::BTreeMap;
+
+// Upper limit for the search, large enough for any real budget.
+const MAX_OUTPUT: usize = 1 << 48;
+const MAX_MIXES: usize = 1 << 16;
+
+impl Plan {
+    pub fn cost(&self, prices: &BTreeMap<String, usize>) -> Result<usize> {
+        self.raw.iter().try_fold(0_usize, |total, (name, count)| {
+            let price = prices
+                .get(name)
+                .ok_or_else(|| format!("no price for {}", name))?;
+            count
+                .checked_mul(*price)
+                .and_then(|cost| total.checked_add(cost))
+                .ok_or_else(|| "cost overflows".into())
+        })
+    }
+}
+
+impl Lab {
+    // Largest amount of `target` that can be made from `stock` alone. The
+    // stock needed never decreases with a larger amount, so the answer is
+    // found by doubling until something runs out and bisecting what's left.
+    pub fn max_output(&self, target: &str, stock: &Stock) -> Result<usize> {
+        let plan = |amount| self.plan_with(target, amount, stock, &Choice::new());
+
+        if !plan(1)?.raw.is_empty() {
+            return Ok(0);
+        }
+
+        // the reactions are fine, so a larger amount can only fail by
+        // needing more than any stock can hold
+        let fits = |amount| plan(amount).is_ok_and(|plan| plan.raw.is_empty());
+
+        let mut low = 1;
+        let mut high = 2;
+        while fits(high) {
+            if high >= MAX_OUTPUT {
+                return Err(format!("{} is not limited by the stock", target).into());
+            }
+
+            low = high;
+            high *= 2;
+        }
+
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if fits(mid) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        Ok(low)
+    }
+
+    // Products with more than one reaction and the indices of those reactions.
+    fn alternatives(&self) -> Vec<(String, Vec<usize>)> {
+        let mut result: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (i, reaction) in self.reactions.iter().enumerate() {
+            result.entry(reaction.to.as_str()).or_default().push(i);
+        }
+
+        result
+            .into_iter()
+            .filter(|(_, reactions)| reactions.len() > 1)
+            .map(|(name, reactions)| (name.to_owned(), reactions))
+            .collect()
+    }
+
+    // Cheapest way to make `amount` of `target` with raw chemicals priced by
+    // `prices`. Every combination of alternative reactions is planned
+    // exactly, so leftovers are accounted for, at the price of being
+    // exponential in the number of products with alternatives.
+    pub fn cheapest(
+        &self,
+        target: &str,
+        amount: usize,
+        prices: &BTreeMap<String, usize>,
+    ) -> Result<(Plan, usize)> {
+        let alternatives = self.alternatives();
+        let mixes = alternatives
+            .iter()
+            .try_fold(1_usize, |a, (_, r)| a.checked_mul(r.len()))
+            .filter(|mixes| *mixes <= MAX_MIXES)
+            .ok_or("too many alternative reactions")?;
+
+        let mut best: Option<(Plan, usize)> = None;
+        let mut error = None;
+
+        for mut n in 0..mixes {
+            let mut choice = Choice::new();
+            for (name, reactions) in alternatives.iter() {
+                choice.insert(name.clone(), reactions[n % reactions.len()]);
+                n /= reactions.len();
+            }
+
+            let plan = match self.plan_with(target, amount, &Stock::new(), &choice) {
+                Ok(plan) => plan,
+                Err(_) => continue,
+            };
+
+            let cost = match plan.cost(prices) {
+                Ok(cost) => cost,
+                Err(e) => {
+                    error = Some(e);
+                    continue;
+                }
+            };
+
+            if best.as_ref().is_none_or(|(_, best)| cost < *best) {
+                best = Some((plan, cost));
+            }
+        }
+
+        // without any priced mix the reason of the last one is as good as any
+        best.ok_or_else(|| error.unwrap_or_else(|| format!("{} can't be produced", target).into()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stock(items: &[(&str, usize)]) -> Stock {
+        items.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn test_max_output() {
+        let lab = Lab::parse("2 ORE, 1 WATER => 1 A\n3 A, 1 WATER => 1 FUEL").unwrap();
+
+        assert_eq!(
+            3,
+            lab.max_output("FUEL", &stock(&[("ORE", 100), ("WATER", 12)]))
+                .unwrap()
+        );
+        assert_eq!(
+            4,
+            lab.max_output("FUEL", &stock(&[("ORE", 24), ("WATER", 100)]))
+                .unwrap()
+        );
+        assert_eq!(
+            12,
+            lab.max_output("A", &stock(&[("ORE", 24), ("WATER", 100)]))
+                .unwrap()
+        );
+        assert_eq!(0, lab.max_output("FUEL", &stock(&[("ORE", 100)])).unwrap());
+
+        // stocked intermediate chemicals are used first
+        let stock = stock(&[("ORE", 6), ("WATER", 100), ("A", 6)]);
+        assert_eq!(3, lab.max_output("FUEL", &stock).unwrap());
+    }
+
+    #[test]
+    fn test_max_output_unbounded() {
+        let lab = Lab::parse("1 ORE => 1 FUEL").unwrap();
+        assert!(lab
+            .max_output("FUEL", &stock(&[("ORE", usize::MAX)]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_cheapest() {
+        let input = r#"
+10 ORE => 1 A
+1 COAL, 1 ORE => 2 A
+3 A => 1 FUEL
+"#;
+        let lab = Lab::parse(input).unwrap();
+
+        let prices = stock(&[("ORE", 1), ("COAL", 100)]);
+        let (plan, cost) = lab.cheapest("FUEL", 1, &prices).unwrap();
+        assert_eq!(30, cost);
+        assert_eq!(Some(&3), plan.batches.get("A"));
+
+        let prices = stock(&[("ORE", 1), ("COAL", 2)]);
+        let (plan, cost) = lab.cheapest("FUEL", 1, &prices).unwrap();
+        assert_eq!(6, cost);
+        assert_eq!(Some(&1), plan.leftovers.get("A"));
+
+        // mixes with unpriced chemicals are skipped
+        let (plan, cost) = lab.cheapest("FUEL", 1, &stock(&[("ORE", 1)])).unwrap();
+        assert_eq!(30, cost);
+        assert_eq!(None, plan.raw.get("COAL"));
+
+        let error = lab.cheapest("FUEL", 1, &stock(&[("COAL", 1)])).unwrap_err();
+        assert_eq!("no price for ORE", error.to_string());
+    }
+
+    #[test]
+    fn test_overflow() {
+        // every step multiplies the demand by 1000
+        let names = ["A", "B", "C", "D", "E", "F", "G", "H", "FUEL"];
+        let mut input = vec!["1000 ORE => 1 A".to_owned()];
+        input.extend(
+            names
+                .windows(2)
+                .map(|w| format!("1000 {} => 1 {}", w[0], w[1])),
+        );
+        let lab = Lab::parse(&input.join("\n")).unwrap();
+
+        assert!(lab.plan("FUEL", 1).is_err());
+        assert!(lab.plan("E", 1).is_ok());
+        assert_eq!(
+            1152,
+            lab.max_output("E", &stock(&[("ORE", 1 << 60)])).unwrap()
+        );
+
+        let prices = stock(&[("ORE", usize::MAX)]);
+        assert!(lab.cheapest("A", 1, &prices).is_err());
+    }
+}
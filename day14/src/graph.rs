@@ -0,0 +1,223 @@
+use crate::Lab;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fmt::Write;
+
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    Undefined(String),
+    Duplicate(String, usize),
+    Unreachable(String),
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use Problem::*;
+
+        match self {
+            Undefined(name) => write!(fmt, "{} is used, but no reaction produces it", name),
+            Duplicate(name, n) => write!(fmt, "{} is produced by {} reactions", name, n),
+            Unreachable(name) => write!(fmt, "{} is never needed", name),
+            Cycle(path) => write!(fmt, "cycle {}", path.join(" -> ")),
+        }
+    }
+}
+
+impl Lab {
+    // `raw` chemicals are available without a reaction, everything else must
+    // be produced by exactly one reaction and be needed for `target`.
+    pub fn validate(&self, target: &str, raw: &[&str]) -> Vec<Problem> {
+        let mut result = vec![];
+
+        let used = self
+            .reactions
+            .iter()
+            .flat_map(|r| r.input.iter().map(|(_, name)| name.as_str()))
+            .chain(Some(target))
+            .collect::<BTreeSet<_>>();
+
+        for name in used {
+            if !self.input.contains_key(name) && !raw.contains(&name) {
+                result.push(Problem::Undefined(name.to_owned()));
+            }
+        }
+
+        let mut producers = BTreeMap::new();
+        for reaction in self.reactions.iter() {
+            *producers.entry(reaction.to.as_str()).or_insert(0) += 1;
+        }
+
+        for (name, count) in producers.iter().filter(|(_, count)| **count > 1) {
+            result.push(Problem::Duplicate(name.to_string(), *count));
+        }
+
+        let mut reached = BTreeSet::new();
+        let mut queue = vec![target];
+        while let Some(name) = queue.pop() {
+            if !reached.insert(name) {
+                continue;
+            }
+
+            for reaction in self.reactions.iter().filter(|r| r.to == name) {
+                queue.extend(reaction.input.iter().map(|(_, name)| name.as_str()));
+            }
+        }
+
+        for name in producers.keys().filter(|name| !reached.contains(*name)) {
+            result.push(Problem::Unreachable(name.to_string()));
+        }
+
+        let mut marks = HashMap::new();
+        for name in producers.keys() {
+            self.find_cycles(name, &mut marks, &mut vec![], &mut result);
+        }
+
+        result
+    }
+
+    fn find_cycles<'a>(
+        &'a self,
+        name: &'a str,
+        marks: &mut HashMap<&'a str, bool>,
+        path: &mut Vec<&'a str>,
+        result: &mut Vec<Problem>,
+    ) {
+        match marks.get(name) {
+            Some(true) => return,
+            Some(false) => {
+                let start = path.iter().position(|n| *n == name).unwrap_or(0);
+                let mut cycle = path[start..]
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>();
+                cycle.push(name.to_owned());
+                result.push(Problem::Cycle(cycle));
+                return;
+            }
+            None => {}
+        }
+
+        marks.insert(name, false);
+        path.push(name);
+
+        for reaction in self.reactions.iter().filter(|r| r.to == name) {
+            for (_, input) in reaction.input.iter() {
+                self.find_cycles(input, marks, path, result);
+            }
+        }
+
+        path.pop();
+        marks.insert(name, true);
+    }
+
+    // Graphviz graph of the reactions, edges go from inputs to products and
+    // are labeled with the amount used per batch.
+    pub fn to_dot(&self) -> String {
+        let mut result = String::new();
+        writeln!(result, "digraph reactions {{").unwrap();
+        writeln!(result, "    rankdir=LR;").unwrap();
+
+        let mut nodes = BTreeSet::new();
+        for reaction in self.reactions.iter() {
+            nodes.insert(reaction.to.as_str());
+            nodes.extend(reaction.input.iter().map(|(_, name)| name.as_str()));
+        }
+
+        for name in nodes {
+            match self.reaction(name) {
+                Some(reaction) => {
+                    let ore = self
+                        .plan(name, reaction.to_count)
+                        .map(|plan| plan.ore().to_string())
+                        .unwrap_or_else(|_| "?".to_owned());
+
+                    writeln!(
+                        result,
+                        "    \"{}\" [label=\"{}\\n{} per batch\\n{} ORE\"];",
+                        name, name, reaction.to_count, ore
+                    )
+                    .unwrap();
+                }
+                None => writeln!(result, "    \"{}\" [shape=box];", name).unwrap(),
+            }
+        }
+
+        for reaction in self.reactions.iter() {
+            for (count, input) in reaction.input.iter() {
+                writeln!(
+                    result,
+                    "    \"{}\" -> \"{}\" [label=\"{}\"];",
+                    input, reaction.to, count
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(result, "}}").unwrap();
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_valid() {
+        let lab = Lab::parse("10 ORE => 10 A\n5 A => 5 B\n7 A, 1 B => 1 FUEL").unwrap();
+        assert_eq!(Vec::<Problem>::new(), lab.validate("FUEL", &["ORE"]));
+    }
+
+    #[test]
+    fn test_problems() {
+        let input = r#"
+10 ORE => 10 A
+1 A, 2 X => 1 FUEL
+3 ORE => 1 A
+1 ORE => 1 JUNK
+1 C => 1 B
+1 B, 1 A => 1 C
+1 C => 1 X
+"#;
+        let lab = Lab::parse(input).unwrap();
+        let problems = lab.validate("FUEL", &["ORE"]);
+
+        assert_eq!(
+            vec![
+                Problem::Duplicate("A".to_owned(), 2),
+                Problem::Unreachable("JUNK".to_owned()),
+                Problem::Cycle(vec!["B".to_owned(), "C".to_owned(), "B".to_owned()]),
+            ],
+            problems
+        );
+
+        let lab = Lab::parse("1 ORE, 2 Q => 1 FUEL").unwrap();
+        assert_eq!(
+            vec![Problem::Undefined("Q".to_owned())],
+            lab.validate("FUEL", &["ORE"])
+        );
+        assert_eq!(
+            vec![
+                Problem::Undefined("ORE".to_owned()),
+                Problem::Undefined("Q".to_owned()),
+                Problem::Undefined("X".to_owned()),
+                Problem::Unreachable("FUEL".to_owned()),
+            ],
+            lab.validate("X", &[])
+        );
+    }
+
+    #[test]
+    fn test_dot() {
+        let lab = Lab::parse("10 ORE => 10 A\n5 A => 5 B\n7 A, 1 B => 1 FUEL").unwrap();
+        let dot = lab.to_dot();
+
+        assert!(dot.starts_with("digraph reactions {\n"));
+        assert!(dot.contains("\"ORE\" [shape=box];"));
+        assert!(dot.contains("\"B\" [label=\"B\\n5 per batch\\n10 ORE\"];"));
+        assert!(dot.contains("\"FUEL\" [label=\"FUEL\\n1 per batch\\n20 ORE\"];"));
+        assert!(dot.contains("\"A\" -> \"FUEL\" [label=\"7\"];"));
+        assert_eq!(4, dot.matches(" -> ").count());
+    }
+}
@@ -1,6 +1,8 @@
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
 use std::fs;
 use std::time::Instant;
 
@@ -18,7 +20,7 @@ struct Reaction {
 impl Reaction {
     pub fn parse(src: &str) -> Result<Self> {
         lazy_static! {
-            static ref RE: Regex = Regex::new(r"(.*) => (.*)").unwrap();
+            static ref RE: Regex = Regex::new(r"^(.*) => (.*)\s*$").unwrap();
         }
         let caps = RE.captures(src).ok_or(format!("input error: {}", src))?;
         let input_str = caps.get(1).ok_or("input error").map(|r| r.as_str())?;
@@ -42,9 +44,11 @@ impl Reaction {
 
     fn parse_part(src: &str) -> Result<(usize, String)> {
         lazy_static! {
-            static ref RE: Regex = Regex::new(r"(\d+) ([A-Z]+)").unwrap();
+            static ref RE: Regex = Regex::new(r"^\s*(\d+) ([A-Z]+)\s*$").unwrap();
         }
-        let caps = RE.captures(src).ok_or("input error")?;
+        let caps = RE
+            .captures(src)
+            .ok_or_else(|| format!("input error: {}", src.trim()))?;
         let count = caps
             .get(1)
             .and_then(|input| input.as_str().parse::<usize>().ok())
@@ -59,179 +63,272 @@ impl Reaction {
     }
 }
 
-#[derive(Debug)]
-struct ReactionInfo {
-    reaction: Reaction,
-    weight: Option<usize>,
+// Amounts of chemicals available before any reaction runs.
+type Stock = BTreeMap<String, usize>;
+
+// Reaction picked for a product when several reactions produce it, as an
+// index into `Lab::reactions`.
+type Choice = HashMap<String, usize>;
+
+// Everything needed to produce the requested amount: how many times each
+// reaction runs, what is left over afterwards, what was taken from the stock
+// and how much of the raw chemicals (the ones without a reaction, like ORE) is
+// still missing.
+#[derive(Debug, Default, PartialEq)]
+struct Plan {
+    batches: BTreeMap<String, usize>,
+    leftovers: BTreeMap<String, usize>,
+    used: BTreeMap<String, usize>,
+    raw: BTreeMap<String, usize>,
 }
 
-impl ReactionInfo {
-    pub fn new(reaction: Reaction) -> Self {
-        Self {
-            reaction: reaction,
-            weight: None,
+impl Plan {
+    pub fn ore(&self) -> usize {
+        self.raw.get("ORE").copied().unwrap_or(0)
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for (name, count) in self.raw.iter() {
+            writeln!(fmt, "{:>12} {}", count, name)?;
+        }
+
+        for (name, count) in self.used.iter() {
+            writeln!(fmt, "{:>12} {} from stock", count, name)?;
         }
+
+        for (name, batches) in self.batches.iter() {
+            match self.leftovers.get(name) {
+                Some(left) => writeln!(fmt, "{:>12} x {}, {} left", batches, name, left)?,
+                None => writeln!(fmt, "{:>12} x {}", batches, name)?,
+            }
+        }
+
+        Ok(())
     }
 }
 
+// All reactions in input order, `input` points to the first reaction for
+// every product. Duplicates are kept so the validator can report them.
 #[derive(Debug)]
 struct Lab {
-    input: HashMap<String, ReactionInfo>,
-    wants: HashMap<String, usize>,
+    reactions: Vec<Reaction>,
+    input: HashMap<String, usize>,
 }
 
 impl Lab {
     pub fn parse(src: &str) -> Result<Self> {
+        let mut reactions = vec![];
         let mut input = HashMap::new();
-        for line in src.lines().filter(|l| l.len() > 0) {
+
+        for line in src.lines().filter(|l| !l.trim().is_empty()) {
             let reaction = Reaction::parse(line)?;
-            input.insert(reaction.to.clone(), ReactionInfo::new(reaction));
+            input.entry(reaction.to.clone()).or_insert(reactions.len());
+            reactions.push(reaction);
         }
 
-        Ok(Self {
-            input,
-            wants: HashMap::new(),
-        })
+        Ok(Self { reactions, input })
+    }
+
+    pub fn reaction(&self, name: &str) -> Option<&Reaction> {
+        self.input.get(name).map(|i| &self.reactions[*i])
     }
 
-    pub fn calculate(&mut self, fuel: usize) -> usize {
-        self.set_weight("FUEL");
-        self.wants.insert("FUEL".into(), fuel);
+    fn pick(&self, name: &str, choice: &Choice) -> Option<&Reaction> {
+        match choice.get(name) {
+            Some(i) => self.reactions.get(*i),
+            None => self.reaction(name),
+        }
+    }
 
-        loop {
-            let (i, total) = self.get_wants();
+    pub fn calculate(&self, fuel: usize) -> Result<usize> {
+        self.plan("FUEL", fuel).map(|plan| plan.ore())
+    }
 
-            if i == "ORE" {
-                return total;
+    // Chemicals needed for `target`, every product before all of its inputs,
+    // so by the time a chemical is reached its total demand is known.
+    fn order(&self, target: &str, choice: &Choice) -> Result<Vec<String>> {
+        fn visit<'a>(
+            lab: &'a Lab,
+            choice: &'a Choice,
+            name: &'a str,
+            marks: &mut HashMap<&'a str, bool>,
+            result: &mut Vec<String>,
+        ) -> Result<()> {
+            match marks.get(name) {
+                Some(true) => return Ok(()),
+                Some(false) => return Err(format!("cycle at {}", name).into()),
+                None => {}
             }
-            self.wants.remove(&i);
-            let info = &self.input.get(&i).expect("A").reaction;
-            let multi = (total as f32 / info.to_count as f32).ceil() as usize;
-            let more_wants: Vec<_> = info
-                .input
-                .iter()
-                .map(|i| (i.0 * multi, i.1.clone()))
-                .collect();
-            self.add_wants(more_wants);
+
+            marks.insert(name, false);
+            if let Some(reaction) = lab.pick(name, choice) {
+                for (_, input) in reaction.input.iter() {
+                    visit(lab, choice, input, marks, result)?;
+                }
+            }
+            marks.insert(name, true);
+            result.push(name.to_owned());
+
+            Ok(())
         }
+
+        let mut result = vec![];
+        visit(self, choice, target, &mut HashMap::new(), &mut result)?;
+        result.reverse();
+
+        Ok(result)
     }
 
-    fn set_weight(&mut self, part: &str) -> usize {
-        if part == "ORE" {
-            return 0;
-        }
+    pub fn plan(&self, target: &str, amount: usize) -> Result<Plan> {
+        self.plan_with(target, amount, &Stock::new(), &Choice::new())
+    }
 
-        let current: Option<usize>;
+    // Stocked chemicals are used up before any reaction producing them runs.
+    pub fn plan_with(
+        &self,
+        target: &str,
+        amount: usize,
+        stock: &Stock,
+        choice: &Choice,
+    ) -> Result<Plan> {
+        let mut plan = Plan::default();
+        let mut wants = HashMap::new();
+        wants.insert(target.to_owned(), amount);
+
+        for name in self.order(target, choice)? {
+            let mut want = wants.remove(&name).unwrap_or(0);
+
+            let take = stock.get(&name).copied().unwrap_or(0).min(want);
+            if take > 0 {
+                plan.used.insert(name.clone(), take);
+                want -= take;
+            }
 
-        let info = self.input.get(part).expect("B");
-        if let Some(weight) = info.weight {
-            return weight;
-        } else {
-            let inputs: Vec<_> = info.reaction.input.iter().map(|i| i.1.to_owned()).collect();
-            let i: Vec<_> = inputs.into_iter().map(|i| self.set_weight(&i)).collect();
-            let max = i.into_iter().max().unwrap_or(0);
-            current = Some(max + 1);
-        }
+            let reaction = match self.pick(&name, choice) {
+                Some(reaction) => reaction,
+                None => {
+                    if want > 0 {
+                        plan.raw.insert(name, want);
+                    }
+                    continue;
+                }
+            };
 
-        self.input.get_mut(part).map(|v| v.weight = current);
-        current.expect("C")
-    }
-
-    fn get_wants(&self) -> (String, usize) {
-        let mut unsorted: Vec<_> = self
-            .wants
-            .keys()
-            .map(|k| k.to_owned())
-            .map(|name| {
-                let weight = self
-                    .input
-                    .get(&name)
-                    .and_then(|info| info.weight)
-                    .unwrap_or(0);
-                (name, weight)
-            })
-            .collect();
-
-        unsorted.sort_by_key(|i| i.1);
-        unsorted.reverse();
-        let wants = unsorted.into_iter().next().expect("A3").0;
-        let value = *self.wants.get(&wants).expect("A4");
-        (wants, value)
-    }
-
-    fn add_wants(&mut self, wants: Vec<(usize, String)>) {
-        for (a, b) in wants {
-            if let Some(c) = self.wants.get_mut(&b) {
-                *c += a;
-            } else {
-                self.wants.insert(b, a);
+            if want == 0 {
+                continue;
             }
+
+            let overflow = || format!("amount of {} overflows", name);
+            let batches = want.div_ceil(reaction.to_count);
+            for (count, input) in reaction.input.iter() {
+                let entry = wants.entry(input.clone()).or_insert(0);
+                *entry = count
+                    .checked_mul(batches)
+                    .and_then(|more| entry.checked_add(more))
+                    .ok_or_else(overflow)?;
+            }
+
+            let made = batches
+                .checked_mul(reaction.to_count)
+                .ok_or_else(overflow)?;
+            let left = made - want;
+            if left > 0 {
+                plan.leftovers.insert(name.clone(), left);
+            }
+            plan.batches.insert(name, batches);
         }
-    }
-}
 
-#[derive(Debug)]
-enum Foo {
-    TooSmall,
-    TooBig,
-    Equal,
+        Ok(plan)
+    }
 }
 
 struct Reverse {
-    input: String,
+    lab: Lab,
 }
 
 impl Reverse {
-    pub fn build(input: &str) -> Self {
-        Self {
-            input: input.into(),
-        }
+    pub fn build(input: &str) -> Result<Self> {
+        let lab = Lab::parse(input)?;
+        Ok(Self { lab })
     }
 
-    pub fn calculate(&self, target: usize) -> usize {
-        let mut t0 = 1;
-        let mut t1 = 1000;
+    pub fn calculate(&self, ore: usize) -> Result<usize> {
+        let mut stock = Stock::new();
+        stock.insert("ORE".to_owned(), ore);
 
-        loop {
-            let result = self.test(t1, target);
-            match result {
-                Foo::Equal => return t1,
-                Foo::TooSmall => {
-                    t0 = t1;
-                    t1 = t1 * 2;
-                }
-                Foo::TooBig => {
-                    let diff = ((t1 as f64 - t0 as f64) / 2.0).ceil() as usize;
-                    t1 = t1 - diff;
-                }
-            }
-        }
+        self.lab.max_output("FUEL", &stock)
     }
+}
 
-    fn test(&self, value: usize, target: usize) -> Foo {
-        let mut lab = Lab::parse(&self.input).unwrap();
-        let result_1 = lab.calculate(value);
-
-        let mut lab = Lab::parse(&self.input).unwrap();
-        let result_2 = lab.calculate(value + 1);
-
-        match (result_1, result_2) {
-            (i, j) if i <= target && j > target => Foo::Equal,
-            (i, j) if i > target && j > target => Foo::TooBig,
-            _ => Foo::TooSmall,
-        }
-    }
+// CHEM=123 pairs from the command line.
+fn parse_amounts(args: &[String]) -> Result<Stock> {
+    args.iter()
+        .map(|arg| {
+            let mut parts = arg.splitn(2, '=');
+            let name = parts.next().unwrap_or("").to_owned();
+            let value = parts
+                .next()
+                .ok_or_else(|| format!("expected CHEM=amount, got {}", arg))?
+                .parse::<usize>()?;
+            Ok((name, value))
+        })
+        .collect()
 }
 
+mod budget;
+mod graph;
+
 fn main() -> Result<()> {
     let now = Instant::now();
-
     let input = fs::read_to_string("input.txt")?;
-    let mut lab = Lab::parse(&input)?;
-    let task_a = lab.calculate(1);
 
-    let r = Reverse::build(&input);
-    let x = r.calculate(TASK_2_TARGE);
+    let ore = match env::args().nth(1).as_deref() {
+        Some("check") => {
+            let problems = Lab::parse(&input)?.validate("FUEL", &["ORE"]);
+            for problem in problems.iter() {
+                println!("{}", problem);
+            }
+            return match problems.len() {
+                0 => Ok(()),
+                n => Err(format!("{} problems found", n).into()),
+            };
+        }
+        Some("dot") => {
+            print!("{}", Lab::parse(&input)?.to_dot());
+            return Ok(());
+        }
+        // day14 max FUEL ORE=1000000 ...
+        Some("max") => {
+            let args = env::args().skip(2).collect::<Vec<_>>();
+            let target = args.first().ok_or("missing target")?;
+            let stock = parse_amounts(&args[1..])?;
+
+            println!("{}", Lab::parse(&input)?.max_output(target, &stock)?);
+            return Ok(());
+        }
+        // day14 cheapest FUEL 1 ORE=1 ...
+        Some("cheapest") => {
+            let args = env::args().skip(2).collect::<Vec<_>>();
+            let target = args.first().ok_or("missing target")?;
+            let amount = args.get(1).ok_or("missing amount")?.parse::<usize>()?;
+            let prices = parse_amounts(&args[2..])?;
+
+            let (plan, cost) = Lab::parse(&input)?.cheapest(target, amount, &prices)?;
+            print!("{}", plan);
+            println!("total cost: {}", cost);
+            return Ok(());
+        }
+        Some(arg) => arg.parse::<usize>()?,
+        None => TASK_2_TARGE,
+    };
+
+    let lab = Lab::parse(&input)?;
+    let task_a = lab.calculate(1)?;
+
+    let r = Reverse::build(&input)?;
+    let x = r.calculate(ore)?;
 
     let total_time = now.elapsed();
 
@@ -310,64 +407,124 @@ mod test {
         assert_eq!(reaction, expected);
     }
 
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Reaction::parse("foo => bar").is_err());
+        assert!(Reaction::parse("1 A B => 2 C").is_err());
+        assert!(Reaction::parse("1 A, 2 B => 2 C junk").is_err());
+        assert!(Lab::parse("1 ORE => 1 A\n\nhello").is_err());
+    }
+
     #[test]
     fn test_lab_a() {
-        let mut lab = Lab::parse("1 ORE => 1 FUEL").unwrap();
-        let result = lab.calculate(1);
+        let lab = Lab::parse("1 ORE => 1 FUEL").unwrap();
+        let result = lab.calculate(1).unwrap();
         assert_eq!(1, result);
     }
     #[test]
     fn test_lab_b() {
-        let mut lab = Lab::parse("10 ORE => 1 FUEL").unwrap();
-        let result = lab.calculate(1);
+        let lab = Lab::parse("10 ORE => 1 FUEL").unwrap();
+        let result = lab.calculate(1).unwrap();
         assert_eq!(10, result);
     }
     #[test]
     fn test_lab_c() {
-        let mut lab = Lab::parse("10 ORE => 1 A\n10 A => 1 FUEL").unwrap();
-        let result = lab.calculate(1);
+        let lab = Lab::parse("10 ORE => 1 A\n10 A => 1 FUEL").unwrap();
+        let result = lab.calculate(1).unwrap();
         assert_eq!(100, result);
     }
     #[test]
     fn test_lab_d() {
-        let mut lab = Lab::parse("10 ORE => 1 A\n10 A => 10 B\n1 A, 1 B => 1 FUEL").unwrap();
-        let result = lab.calculate(1);
+        let lab = Lab::parse("10 ORE => 1 A\n10 A => 10 B\n1 A, 1 B => 1 FUEL").unwrap();
+        let result = lab.calculate(1).unwrap();
         assert_eq!(110, result);
     }
     #[test]
     fn test_lab_e() {
-        let mut lab = Lab::parse("10 ORE => 10 A\n5 A => 5 B\n1 A, 1 B => 1 FUEL").unwrap();
-        let result = lab.calculate(1);
+        let lab = Lab::parse("10 ORE => 10 A\n5 A => 5 B\n1 A, 1 B => 1 FUEL").unwrap();
+        let result = lab.calculate(1).unwrap();
         assert_eq!(10, result);
     }
     #[test]
     fn test_lab_13312() {
-        let mut lab = Lab::parse(example_13312()).unwrap();
-        let result = lab.calculate(1);
+        let lab = Lab::parse(example_13312()).unwrap();
+        let result = lab.calculate(1).unwrap();
         assert_eq!(13312, result);
     }
 
     #[test]
     fn test_lab_180697() {
-        let mut lab = Lab::parse(example_180697()).unwrap();
-        let result = lab.calculate(1);
+        let lab = Lab::parse(example_180697()).unwrap();
+        let result = lab.calculate(1).unwrap();
         assert_eq!(180697, result);
     }
     #[test]
     fn test_lab_2210736() {
-        let mut lab = Lab::parse(example_2210736()).unwrap();
-        let result = lab.calculate(1);
+        let lab = Lab::parse(example_2210736()).unwrap();
+        let result = lab.calculate(1).unwrap();
         assert_eq!(2210736, result);
     }
 
     #[test]
     fn test_reverse_13312() {
-        let reverse = Reverse::build(example_13312());
-        assert_eq!(82892753, reverse.calculate(TASK_2_TARGE));
+        let reverse = Reverse::build(example_13312()).unwrap();
+        assert_eq!(82892753, reverse.calculate(TASK_2_TARGE).unwrap());
+    }
+    #[test]
+    fn test_reverse_180697() {
+        let reverse = Reverse::build(example_180697()).unwrap();
+        assert_eq!(5586022, reverse.calculate(TASK_2_TARGE).unwrap());
     }
+
     #[test]
     fn test_reverse_2210736() {
-        let reverse = Reverse::build(example_2210736());
-        assert_eq!(460664, reverse.calculate(TASK_2_TARGE));
+        let reverse = Reverse::build(example_2210736()).unwrap();
+        assert_eq!(460664, reverse.calculate(TASK_2_TARGE).unwrap());
+    }
+
+    #[test]
+    fn test_plan() {
+        let lab = Lab::parse("10 ORE => 10 A\n5 A => 5 B\n7 A, 1 B => 1 FUEL").unwrap();
+        let plan = lab.plan("FUEL", 1).unwrap();
+
+        assert_eq!(Some(&1), plan.batches.get("FUEL"));
+        assert_eq!(Some(&1), plan.batches.get("B"));
+        assert_eq!(Some(&2), plan.batches.get("A"));
+        assert_eq!(Some(&4), plan.leftovers.get("B"));
+        assert_eq!(Some(&8), plan.leftovers.get("A"));
+        assert_eq!(20, plan.ore());
+    }
+
+    #[test]
+    fn test_plan_exact() {
+        let lab = Lab::parse(example_13312()).unwrap();
+        let fuel = 82892753;
+        let plan = lab.plan("FUEL", fuel).unwrap();
+
+        assert!(plan.ore() <= TASK_2_TARGE);
+        assert!(lab.plan("FUEL", fuel + 1).unwrap().ore() > TASK_2_TARGE);
+        assert_eq!(Some(&fuel), plan.batches.get("FUEL"));
+    }
+
+    #[test]
+    fn test_plan_cycle() {
+        let lab = Lab::parse("1 B => 1 A\n1 A, 1 ORE => 1 B\n1 A => 1 FUEL").unwrap();
+        assert!(lab.plan("FUEL", 1).is_err());
+        assert!(lab.calculate(1).is_err());
+
+        let reverse = Reverse::build("1 B => 1 A\n1 A, 1 ORE => 1 B\n1 A => 1 FUEL").unwrap();
+        assert!(reverse.calculate(10).is_err());
+    }
+
+    #[test]
+    fn test_reverse_budget() {
+        let reverse = Reverse::build(example_13312()).unwrap();
+        assert_eq!(0, reverse.calculate(13311).unwrap());
+        assert_eq!(1, reverse.calculate(13312).unwrap());
+
+        let reverse = Reverse::build("10 ORE => 1 FUEL").unwrap();
+        assert_eq!(0, reverse.calculate(9).unwrap());
+        assert_eq!(12, reverse.calculate(129).unwrap());
+        assert_eq!(1 << 40, reverse.calculate(10 << 40).unwrap());
     }
 }
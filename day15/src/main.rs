@@ -1,6 +1,9 @@
 use std::fs;
 use vm::CPU;
 
+#[cfg(test)]
+#[path = "../../intcode/conformance.rs"]
+mod conformance;
 mod vm;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
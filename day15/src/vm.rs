@@ -428,4 +428,20 @@ mod test {
 
         assert_eq!(code, output);
     }
+
+    #[test]
+    fn test_conformance() {
+        crate::conformance::check(&["day02", "day05", "day09"], |programm, input| {
+            let mut input = input.iter().copied();
+            let mut output = vec![];
+            let io = IO::new(
+                move || input.next().expect("input is empty"),
+                |v| output.push(v),
+            );
+            let mut cpu = CPU::new(programm.to_vec());
+            cpu.run(io);
+
+            (output, cpu.mem.raw)
+        });
+    }
 }
@@ -1,79 +1,101 @@
+use std::env;
 use std::fs;
 use std::iter;
+use std::time::Instant;
 
 const PATTERN: [i32; 4] = [0, 1, 0, -1];
-const PATTERN_SIZE: usize = 4;
 const REPEAT: usize = 10000;
+const PHASES: usize = 100;
+// keeps the sums of a phase within i64 for signals of a few million digits
+const MAX_WEIGHT: i32 = 1000;
 
 #[derive(Debug, PartialEq)]
 enum Take {
     Pos(usize, usize),
     Neg(usize, usize),
+    Mul(i32, usize, usize),
 }
 
 impl Take {
+    pub fn new(weight: i32, a: usize, b: usize) -> Self {
+        match weight {
+            1 => Take::Pos(a, b),
+            -1 => Take::Neg(a, b),
+            n => Take::Mul(n, a, b),
+        }
+    }
+
     pub fn range(&self) -> (usize, usize) {
         use Take::*;
         match self {
             Pos(a, b) => (*a, *b),
             Neg(a, b) => (*a, *b),
+            Mul(_, a, b) => (*a, *b),
         }
     }
-}
 
-enum NextStep {
-    Pos(usize),
-    Neg(usize),
-    None,
+    pub fn weight(&self) -> i32 {
+        use Take::*;
+        match self {
+            Pos(_, _) => 1,
+            Neg(_, _) => -1,
+            Mul(n, _, _) => *n,
+        }
+    }
+
+    pub fn sum(&self, data: &[i32]) -> i64 {
+        data.iter().map(|v| *v as i64).sum::<i64>() * self.weight() as i64
+    }
 }
 
-struct Pattern {
+// Every element of the base pattern is repeated `step + 1` times and the very
+// first value is skipped, so block `n` covers `[n * (step + 1) - 1, (n + 1) *
+// (step + 1) - 1)`. Blocks with zero weight are not reported.
+struct Pattern<'a> {
+    base: &'a [i32],
     limit: usize,
     step: usize,
-    next_step: NextStep,
+    block: usize,
 }
 
-impl Pattern {
+impl Pattern<'static> {
+    #[allow(dead_code)]
     pub fn new(step: usize, limit: usize) -> Self {
-        let first_pos = step;
-        let next_step = match first_pos {
-            n if n < limit => NextStep::Pos(n),
-            _ => NextStep::None,
-        };
+        Pattern::with_base(step, limit, &PATTERN)
+    }
+}
+
+impl<'a> Pattern<'a> {
+    pub fn with_base(step: usize, limit: usize, base: &'a [i32]) -> Self {
+        assert!(!base.is_empty());
 
         Self {
+            base,
             step,
             limit,
-            next_step,
+            block: 0,
         }
     }
 }
 
-impl Iterator for Pattern {
+impl<'a> Iterator for Pattern<'a> {
     type Item = Take;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.next_step {
-            NextStep::None => None,
-            NextStep::Pos(n) => {
-                let current_start = n;
-                let current_stop = (n + self.step + 1).min(self.limit);
-                self.next_step = match n + (self.step + 1) * 2 {
-                    n if n < self.limit => NextStep::Neg(n),
-                    _ => NextStep::None,
-                };
-
-                Some(Take::Pos(current_start, current_stop))
+        let width = self.step + 1;
+
+        loop {
+            let start = (self.block * width).saturating_sub(1);
+            if start >= self.limit {
+                return None;
             }
-            NextStep::Neg(n) => {
-                let current_start = n;
-                let current_stop = (n + self.step + 1).min(self.limit);
-                self.next_step = match n + (self.step + 1) * 2 {
-                    n if n < self.limit => NextStep::Pos(n),
-                    _ => NextStep::None,
-                };
-
-                Some(Take::Neg(current_start, current_stop))
+
+            let stop = ((self.block + 1) * width - 1).min(self.limit);
+            let weight = self.base[self.block % self.base.len()];
+            self.block += 1;
+
+            if weight != 0 && start < stop {
+                return Some(Take::new(weight, start, stop));
             }
         }
     }
@@ -119,11 +141,88 @@ impl DataRange {
 
         &self.data[i..j]
     }
+
+    pub fn first_n(&self, n: usize) -> String {
+        self.data
+            .iter()
+            .take(n)
+            .fold(String::new(), |a, e| format!("{}{}", a, e))
+    }
+}
+
+#[derive(Debug)]
+struct Config {
+    pattern: Vec<i32>,
+    repeat: usize,
+    phases: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            pattern: PATTERN.to_vec(),
+            repeat: REPEAT,
+            phases: PHASES,
+        }
+    }
+}
+
+impl Config {
+    // --pattern 0,1,0,-1 --repeat 10000 --phases 100
+    pub fn from_args(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut args = args;
+
+        while let Some(key) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", key))?;
+
+            match key.as_str() {
+                "--pattern" => {
+                    config.pattern = value
+                        .split(',')
+                        .map(|v| v.trim().parse::<i32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|e| format!("invalid pattern {}: {}", value, e))?;
+
+                    if config.pattern.is_empty() {
+                        return Err("empty pattern".to_owned());
+                    }
+
+                    if config
+                        .pattern
+                        .iter()
+                        .any(|v| !(-MAX_WEIGHT..=MAX_WEIGHT).contains(v))
+                    {
+                        return Err(format!(
+                            "invalid pattern {}: values must be within ±{}",
+                            value, MAX_WEIGHT
+                        ));
+                    }
+                }
+                "--repeat" => {
+                    config.repeat = value
+                        .parse()
+                        .map_err(|e| format!("invalid repeat {}: {}", value, e))?
+                }
+                "--phases" => {
+                    config.phases = value
+                        .parse()
+                        .map_err(|e| format!("invalid phases {}: {}", value, e))?
+                }
+                _ => return Err(format!("unknown option {}", key)),
+            }
+        }
+
+        Ok(config)
+    }
 }
 
 #[derive(Debug, PartialEq)]
 struct Signal {
     data: Vec<i32>,
+    base: Vec<i32>,
 }
 
 impl Signal {
@@ -132,17 +231,29 @@ impl Signal {
             .split("")
             .filter_map(|ch| ch.parse::<i32>().ok())
             .collect::<Vec<_>>();
-        Self { data }
+        Self {
+            data,
+            base: PATTERN.to_vec(),
+        }
     }
 
-    pub fn parse_10k(input: &str) -> Self {
+    pub fn parse_repeated(input: &str, repeat: usize) -> Self {
         let single = input.split("").filter_map(|ch| ch.parse::<i32>().ok());
         let data = iter::repeat(single)
-            .take(REPEAT)
+            .take(repeat)
             .flatten()
             .collect::<Vec<_>>();
 
-        Self { data }
+        Self {
+            data,
+            base: PATTERN.to_vec(),
+        }
+    }
+
+    pub fn with_base(mut self, base: &[i32]) -> Self {
+        assert!(!base.is_empty());
+        self.base = base.to_vec();
+        self
     }
 
     pub fn len(&self) -> usize {
@@ -150,19 +261,15 @@ impl Signal {
     }
 
     pub fn next(mut self) -> Self {
-        let mut total = 0;
         let next_data: Vec<_> = (0..self.len())
             .map(|step| {
-                total = 0;
-                let full = Pattern::new(step, self.len())
-                    .map(|pat| match pat {
-                        Take::Pos(a, b) => self.data[a..b].iter().sum::<i32>(),
-                        Take::Neg(a, b) => self.data[a..b].iter().sum::<i32>() * -1,
+                let full = Pattern::with_base(step, self.len(), &self.base)
+                    .map(|pat| {
+                        let (a, b) = pat.range();
+                        pat.sum(&self.data[a..b])
                     })
-                    .inspect(|_| total += 1)
-                    .sum::<i32>();
-                dbg!(total);
-                full.abs() % 10
+                    .sum::<i64>();
+                (full.abs() % 10) as i32
             })
             .collect();
 
@@ -171,6 +278,11 @@ impl Signal {
         self
     }
 
+    pub fn phases(mut self, n: usize) -> Self {
+        self.data = phase::run(self.data, n, &self.base);
+        self
+    }
+
     pub fn first_n(&self, n: usize) -> String {
         self.data
             .iter()
@@ -178,76 +290,156 @@ impl Signal {
             .fold(String::new(), |a, e| format!("{}{}", a, e))
     }
 
+    // Lazy evaluation of `req` after `iter` phases: only the part of the
+    // previous phase covered by the patterns of the requested digits is
+    // computed. Works for any offset, but close to the start of the signal it
+    // is as slow as the full computation.
     pub fn range(&self, req: RangeRequest, iter: usize) -> DataRange {
-        println!("Range, iter: {}", iter);
         if iter == 0 {
             let data = self.data[req.start..(req.start + req.len)].to_vec();
             return DataRange {
-                data: data,
+                data,
                 offset: req.start,
             };
         }
 
-        let takes = (req.start..=(req.start + req.len))
-            .map(|n| Pattern::new(n, self.len()).collect::<Vec<_>>())
-            .flatten()
+        let takes = (req.start..(req.start + req.len))
+            .flat_map(|n| Pattern::with_base(n, self.len(), &self.base))
             .collect::<Vec<_>>();
 
-        dbg!(&takes);
+        if takes.is_empty() {
+            return DataRange {
+                data: vec![0; req.len],
+                offset: req.start,
+            };
+        }
 
         let next_req = RangeRequest::from_takes(&takes[..]);
-        dbg!(&next_req);
-        unimplemented!();
-
         let range = self.range(next_req, iter - 1);
 
-        let next_data: Vec<_> = (req.start..=(req.start + req.len))
+        let next_data: Vec<_> = (req.start..(req.start + req.len))
             .map(|step| {
-                let full = Pattern::new(step, self.len())
-                    .map(|pat| match pat {
-                        Take::Pos(a, b) => range.get(a, b).iter().sum::<i32>(),
-                        Take::Neg(a, b) => range.get(a, b).iter().sum::<i32>() * -1,
+                let full = Pattern::with_base(step, self.len(), &self.base)
+                    .map(|pat| {
+                        let (a, b) = pat.range();
+                        pat.sum(range.get(a, b))
                     })
-                    .sum::<i32>();
+                    .sum::<i64>();
 
-                full.abs() % 10
+                (full.abs() % 10) as i32
             })
             .collect();
 
-        println!("DONE ITER {}", iter);
-
-        return DataRange {
+        DataRange {
             data: next_data,
             offset: req.start,
-        };
+        }
+    }
+
+    // With a leading zero in the base pattern the second half of the signal
+    // only sees the second element of it, repeated up to the end. Every phase
+    // is then a suffix sum.
+    pub fn has_tail(&self, start: usize) -> bool {
+        self.base[0] == 0 && start * 2 >= self.len()
+    }
+
+    pub fn tail(&self, start: usize, iter: usize) -> DataRange {
+        assert!(self.has_tail(start));
+
+        let weight = (self.base[1 % self.base.len()] as i64).abs();
+        let mut data = self.data[start..].to_vec();
+
+        for _ in 0..iter {
+            let mut total = 0;
+            for value in data.iter_mut().rev() {
+                total = (total + *value) % 10;
+                *value = (total as i64 * weight % 10) as i32;
+            }
+        }
+
+        DataRange {
+            data,
+            offset: start,
+        }
     }
 }
 
-fn task_a(input: &str) -> String {
-    let mut input = Signal::parse(&input);
+fn task_a(input: &str, config: &Config) -> String {
+    Signal::parse(&input)
+        .with_base(&config.pattern)
+        .phases(config.phases)
+        .first_n(8)
+}
 
-    for _ in 0..100 {
-        input = input.next();
+fn task_b(input: &str, config: &Config) -> Result<String, String> {
+    let input = Signal::parse_repeated(&input, config.repeat).with_base(&config.pattern);
+    let offset = input
+        .first_n(7)
+        .parse::<usize>()
+        .map_err(|_| "signal is too short for a message offset".to_owned())?;
+    if offset + 8 > input.len() {
+        return Err(format!("offset {} is out of signal", offset));
     }
 
-    input.first_n(8)
+    let range = if input.has_tail(offset) {
+        input.tail(offset, config.phases)
+    } else {
+        input.range(RangeRequest::new(offset, 8), config.phases)
+    };
+
+    Ok(range.first_n(8))
 }
 
-fn task_b(input: &str) -> String {
-    let input = Signal::parse_10k(&input);
-    let offset = input.first_n(7).parse::<usize>().unwrap();
-    let req = RangeRequest::new(offset, 8);
-    let range = input.range(req, 100);
+fn bench(input: &str, config: &Config) {
+    for repeat in [1, 10].iter() {
+        let signal = Signal::parse_repeated(input, *repeat).with_base(&config.pattern);
+        println!("{} digits, {} phases", signal.len(), config.phases);
+
+        let start = Instant::now();
+        let mut naive = Signal::parse_repeated(input, *repeat).with_base(&config.pattern);
+        for _ in 0..config.phases {
+            naive = naive.next();
+        }
+        println!("  naive : {:?}", start.elapsed());
 
-    dbg!(range);
+        let start = Instant::now();
+        let fast = signal.phases(config.phases);
+        println!("  prefix: {:?}", start.elapsed());
 
-    "foo".to_owned()
+        assert_eq!(naive, fast);
+    }
 }
 
+mod phase;
+
 fn main() {
     let raw = fs::read_to_string("input.txt").expect("cant read input.txt");
-    // println!("task I : {}", task_a(&raw));
-    println!("task II: {}", task_b(&raw));
+
+    let mut args = env::args().skip(1).peekable();
+    let is_bench = args.peek().map(|arg| arg == "bench") == Some(true);
+    if is_bench {
+        args.next();
+    }
+
+    let config = Config::from_args(args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        eprintln!("usage: day16 [bench] [--pattern 0,1,0,-1] [--repeat 10000] [--phases 100]");
+        std::process::exit(1);
+    });
+
+    if is_bench {
+        bench(&raw, &config);
+        return;
+    }
+
+    println!("task I : {}", task_a(&raw, &config));
+    match task_b(&raw, &config) {
+        Ok(message) => println!("task II: {}", message),
+        Err(e) => {
+            eprintln!("task II: {}", e);
+            std::process::exit(1);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -277,6 +469,7 @@ mod test {
         let parsed = Signal::parse(&input);
         let expected = Signal {
             data: vec![1, 1, 2],
+            base: vec![0, 1, 0, -1],
         };
 
         assert_eq!(expected, parsed);
@@ -288,16 +481,187 @@ mod test {
         assert_eq!(Signal::parse("34040438").next(), Signal::parse("03415518"));
     }
 
+    #[test]
+    fn test_signal_phases() {
+        let input = "80871224585914546619083218645595";
+        let mut naive = Signal::parse(input);
+        for _ in 0..10 {
+            naive = naive.next();
+        }
+
+        assert_eq!(naive, Signal::parse(input).phases(10));
+    }
+
     #[test]
     fn test_task_1() {
-        assert_eq!(task_a("80871224585914546619083218645595\n"), "24176176");
-        assert_eq!(task_a("19617804207202209144916044189917\n"), "73745418");
-        assert_eq!(task_a("69317163492948606335995924319873\n"), "52432133");
+        assert_eq!(
+            task_a("80871224585914546619083218645595\n", &Config::default()),
+            "24176176"
+        );
+        assert_eq!(
+            task_a("19617804207202209144916044189917\n", &Config::default()),
+            "73745418"
+        );
+        assert_eq!(
+            task_a("69317163492948606335995924319873\n", &Config::default()),
+            "52432133"
+        );
+    }
+    #[test]
+    fn test_range() {
+        let signal = Signal::parse("80871224585914546619083218645595");
+        let range = signal.range(RangeRequest::new(0, 8), 100);
+        assert_eq!(range.first_n(8), "24176176");
+
+        let signal = Signal::parse_repeated("12345678", 3);
+        let mut expected = Signal::parse_repeated("12345678", 3);
+        for _ in 0..4 {
+            expected = expected.next();
+        }
+
+        let range = signal.range(RangeRequest::new(5, 10), 4);
+        assert_eq!(range.data, &expected.data[5..15]);
+
+        let range = signal.tail(12, 4);
+        assert_eq!(range.data, &expected.data[12..]);
     }
+
     #[test]
     fn test_task_2() {
-        assert_eq!(task_b("03036732577212944063491565474664\n"), "84462026");
-        assert_eq!(task_b("02935109699940807407585447034323\n"), "78725270");
-        assert_eq!(task_b("03081770884921959731165446850517\n"), "53553731");
+        assert_eq!(
+            task_b("03036732577212944063491565474664\n", &Config::default()).unwrap(),
+            "84462026"
+        );
+        assert_eq!(
+            task_b("02935109699940807407585447034323\n", &Config::default()).unwrap(),
+            "78725270"
+        );
+        assert_eq!(
+            task_b("03081770884921959731165446850517\n", &Config::default()).unwrap(),
+            "53553731"
+        );
+    }
+
+    #[test]
+    fn test_task_b_offsets() {
+        // offset 12 lies in the first half of the repeated signal
+        let config = Config {
+            repeat: 2,
+            ..Config::default()
+        };
+        let input = "00000121234567890123";
+        let signal = Signal::parse_repeated(input, 2);
+        let expected = reference(&signal.data, &PATTERN, PHASES)[12..20]
+            .iter()
+            .map(|digit| digit.to_string())
+            .collect::<String>();
+        assert_eq!(Ok(expected), task_b(input, &config));
+
+        assert_eq!(
+            Err("offset 9999999 is out of signal".to_owned()),
+            task_b("99999991234\n", &Config::default())
+        );
+        assert!(task_b("123\n", &Config::default()).is_err());
+    }
+
+    // Straight from the definition, no blocks and no prefix sums.
+    fn reference(data: &[i32], base: &[i32], phases: usize) -> Vec<i32> {
+        let mut data = data.to_vec();
+
+        for _ in 0..phases {
+            data = (0..data.len())
+                .map(|pos| {
+                    let total = data
+                        .iter()
+                        .enumerate()
+                        .map(|(i, value)| {
+                            *value as i64 * base[(i + 1) / (pos + 1) % base.len()] as i64
+                        })
+                        .sum::<i64>();
+                    (total.abs() % 10) as i32
+                })
+                .collect();
+        }
+
+        data
+    }
+
+    #[test]
+    fn test_synthetic_patterns() {
+        let input = "5973857340129848193738201848576";
+        let bases: &[&[i32]] = &[
+            &[0, 1, 0, -1],
+            &[1, 2, 3],
+            &[0, 1, 1, 0, -2],
+            &[0, 3, -1],
+            &[2],
+        ];
+
+        for base in bases {
+            let signal = Signal::parse_repeated(input, 3).with_base(base);
+            let expected = reference(&signal.data, base, 5);
+
+            let mut naive = Signal::parse_repeated(input, 3).with_base(base);
+            for _ in 0..5 {
+                naive = naive.next();
+            }
+            assert_eq!(expected, naive.data, "next {:?}", base);
+
+            let range = signal.range(RangeRequest::new(7, 20), 5);
+            assert_eq!(&expected[7..27], &range.data[..], "range {:?}", base);
+
+            if signal.has_tail(60) {
+                assert_eq!(
+                    &expected[60..],
+                    &signal.tail(60, 5).data[..],
+                    "tail {:?}",
+                    base
+                );
+            }
+
+            assert_eq!(expected, signal.phases(5).data, "phases {:?}", base);
+        }
+    }
+
+    #[test]
+    fn test_config() {
+        let args = vec!["--pattern", "1, 0,-2", "--phases", "3"];
+        let config = Config::from_args(args.into_iter().map(String::from)).unwrap();
+
+        assert_eq!(vec![1, 0, -2], config.pattern);
+        assert_eq!(3, config.phases);
+        assert_eq!(REPEAT, config.repeat);
+
+        let args = vec!["--repeat"];
+        assert!(Config::from_args(args.into_iter().map(String::from)).is_err());
+
+        let args = vec!["--pattern", "0,1,0,-2147483648"];
+        assert!(Config::from_args(args.into_iter().map(String::from)).is_err());
+    }
+
+    #[test]
+    fn test_large_pattern() {
+        let args = vec!["--pattern", "999,998"];
+        let config = Config::from_args(args.into_iter().map(String::from)).unwrap();
+        let base = &config.pattern;
+
+        // every sum is about 9 * 999 * 300000, past the range of i32
+        let input = "9".repeat(300_000);
+        let signal = Signal::parse(&input).with_base(base);
+
+        let expected = (0..8)
+            .map(|pos| {
+                let total = signal
+                    .data
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| *value as i64 * base[(i + 1) / (pos + 1) % 2] as i64)
+                    .sum::<i64>();
+                (total.abs() % 10) as i32
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(expected, signal.range(RangeRequest::new(0, 8), 1).data);
+        assert_eq!(expected, signal.phases(1).data[..8].to_vec());
     }
 }
@@ -0,0 +1,139 @@
+use rayon::prelude::*;
+
+// Positions are processed in chunks so the short, expensive patterns at the
+// start of the signal don't end up on a single thread.
+const MIN_CHUNK: usize = 256;
+
+pub fn prefix_sums(data: &[i32]) -> Vec<i64> {
+    let mut result = Vec::with_capacity(data.len() + 1);
+    let mut total = 0;
+
+    result.push(0);
+    for value in data {
+        total += *value as i64;
+        result.push(total);
+    }
+
+    result
+}
+
+// Sum of every `stride`-th prefix sum from `start` on. No branches and no
+// clamping inside, so the loop is a plain strided reduction.
+fn strided(prefix: &[i64], start: usize, stride: usize) -> i64 {
+    prefix
+        .get(start..)
+        .map_or(0, |rest| rest.iter().step_by(stride).sum())
+}
+
+// Output digit at `pos`: every element of the base is repeated `pos + 1`
+// times, element `k` starts at `k * (pos + 1)` and comes back every
+// `base.len() * (pos + 1)` cells. Positions are shifted by one to account for
+// the skipped first value of the pattern.
+//
+// Instead of summing every block as `end - start`, the prefix sum at every
+// block boundary is weighted by the weight of the block ending there minus
+// the one starting there. Boundaries of the same pattern element share that
+// weight and lie `stride` apart, so each element is one strided pass. A
+// block running past the end is closed with the full sum.
+pub fn digit(prefix: &[i64], pos: usize, base: &[i32]) -> i32 {
+    let len = prefix.len() - 1;
+    let width = pos + 1;
+    let stride = width * base.len();
+
+    // boundary `m` sits in front of shifted cell `m * width`, the last one
+    // within the signal starts a block that ends with it
+    let last = (len + 1) / width;
+    let mut total = base[last % base.len()] as i64 * prefix[len];
+
+    for (k, weight) in base.iter().enumerate() {
+        let before = base[(k + base.len() - 1) % base.len()];
+        let coefficient = before as i64 - *weight as i64;
+        if coefficient == 0 {
+            continue;
+        }
+
+        // boundary 0 is the start of the signal, its prefix sum is zero
+        let first = if k == 0 { base.len() } else { k };
+        let sum = strided(prefix, first * width - 1, stride);
+
+        total += coefficient * sum;
+    }
+
+    (total.abs() % 10) as i32
+}
+
+// One phase in O(n log n): the patterns for all positions together cover
+// n / 1 + n / 2 + ... + n / n blocks, each summed in O(1).
+pub fn phase(data: &[i32], base: &[i32]) -> Vec<i32> {
+    let prefix = prefix_sums(data);
+
+    (0..data.len())
+        .into_par_iter()
+        .with_min_len(MIN_CHUNK)
+        .map(|pos| digit(&prefix, pos, base))
+        .collect()
+}
+
+pub fn run(mut data: Vec<i32>, phases: usize, base: &[i32]) -> Vec<i32> {
+    for _ in 0..phases {
+        data = phase(&data, base);
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_prefix_sums() {
+        assert_eq!(vec![0, 1, 3, 6], prefix_sums(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn test_phase() {
+        let data = [1, 2, 3, 4, 5, 6, 7, 8];
+        let expected = [
+            vec![4, 8, 2, 2, 6, 1, 5, 8],
+            vec![3, 4, 0, 4, 0, 4, 3, 8],
+            vec![0, 3, 4, 1, 5, 5, 1, 8],
+            vec![0, 1, 0, 2, 9, 4, 9, 8],
+        ];
+
+        for (i, expected) in expected.iter().enumerate() {
+            assert_eq!(expected, &run(data.to_vec(), i + 1, &[0, 1, 0, -1]));
+        }
+    }
+
+    #[test]
+    fn test_digit() {
+        let prefix = prefix_sums(&[1, 2, 3, 4, 5, 6]);
+
+        // 1 2 0 1 2 0
+        assert_eq!(9, digit(&prefix, 0, &[0, 1, 2]));
+        // 0 0 1 1 1 2
+        assert_eq!(4, digit(&prefix, 2, &[0, 1, 2]));
+        // 1 2 2 3 3 1
+        assert_eq!(4, digit(&prefix, 1, &[1, 2, 3]));
+    }
+
+    #[test]
+    fn test_digit_edges() {
+        // every length and position, so blocks end anywhere around the end
+        let data = (0..40).map(|i| (i * 7 + 3) % 10).collect::<Vec<i32>>();
+        for base in [&[0, 1, 0, -1][..], &[3, -2], &[1, 0, 0, 2, -1]].iter() {
+            for len in 1..data.len() {
+                let prefix = prefix_sums(&data[..len]);
+                for pos in 0..len {
+                    let total = data[..len]
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| v * base[(i + 1) / (pos + 1) % base.len()])
+                        .sum::<i32>();
+                    assert_eq!(total.abs() % 10, digit(&prefix, pos, base));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+// Data driven Intcode conformance suite.
+//
+// Every file in `intcode/conformance` is one case, named after the day that
+// introduced the instructions it needs:
+//
+//     # comment
+//     program: 3,0,4,0,99
+//     input: 42
+//     output: 42
+//     memory: 42,0,4,0,99
+//
+// Each `input:` line starts a new run of the same program; `output:` and
+// `memory:` belong to the latest run. `memory` is compared as a prefix, so
+// machines are free to grow their memory past the program end.
+
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default)]
+pub struct Run {
+    pub input: Vec<i64>,
+    pub output: Option<Vec<i64>>,
+    pub memory: Option<Vec<i64>>,
+}
+
+#[derive(Debug)]
+pub struct Case {
+    pub name: String,
+    pub programm: Vec<i64>,
+    pub runs: Vec<Run>,
+}
+
+fn parse_values(input: &str) -> Vec<i64> {
+    input
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse::<i64>().expect("invalid number"))
+        .collect()
+}
+
+impl Case {
+    pub fn parse(name: &str, input: &str) -> Self {
+        let mut programm = None;
+        let mut runs: Vec<Run> = vec![];
+
+        for line in input.lines().map(|l| l.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, ':');
+            let key = parts.next().unwrap_or("").trim();
+            let values = parse_values(parts.next().unwrap_or(""));
+
+            if key == "input" || (key != "program" && runs.is_empty()) {
+                runs.push(Run::default());
+            }
+
+            let run = runs.last_mut();
+            match (key, run) {
+                ("program", _) => programm = Some(values),
+                ("input", Some(run)) => run.input = values,
+                ("output", Some(run)) => run.output = Some(values),
+                ("memory", Some(run)) => run.memory = Some(values),
+                (key, _) => panic!("{}: unknown key {}", name, key),
+            }
+        }
+
+        if runs.is_empty() {
+            runs.push(Run::default());
+        }
+
+        Self {
+            name: name.to_owned(),
+            programm: programm.unwrap_or_else(|| panic!("{}: program not found", name)),
+            runs,
+        }
+    }
+}
+
+pub fn cases() -> Vec<Case> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../intcode/conformance");
+    let mut paths = fs::read_dir(&dir)
+        .expect("can't read conformance dir")
+        .map(|entry| entry.expect("can't read entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy();
+            let raw = fs::read_to_string(path).expect("can't read case");
+            Case::parse(&name, &raw)
+        })
+        .collect()
+}
+
+// Runs every case through `exec`, which gets the program and the input and
+// returns the produced output and the final memory. Only cases named after
+// one of `days`, the instruction sets the machine implements, are run.
+pub fn check<F>(days: &[&str], exec: F)
+where
+    F: Fn(&[i64], &[i64]) -> (Vec<i64>, Vec<i64>),
+{
+    let cases = cases()
+        .into_iter()
+        .filter(|case| {
+            days.iter()
+                .any(|day| case.name.starts_with(&format!("{}-", day)))
+        })
+        .collect::<Vec<_>>();
+    assert!(!cases.is_empty(), "no conformance cases found");
+
+    let mut failures = vec![];
+
+    for case in cases.iter() {
+        for (i, run) in case.runs.iter().enumerate() {
+            let (output, memory) = exec(&case.programm, &run.input);
+
+            if let Some(expected) = &run.output {
+                if expected != &output {
+                    failures.push(format!(
+                        "{} #{}: output {:?}, expected {:?}",
+                        case.name, i, output, expected
+                    ));
+                }
+            }
+
+            if let Some(expected) = &run.memory {
+                let prefix = &memory[..expected.len().min(memory.len())];
+                if expected.as_slice() != prefix {
+                    failures.push(format!(
+                        "{} #{}: memory {:?}, expected {:?}",
+                        case.name, i, prefix, expected
+                    ));
+                }
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_parse_case() {
+    let raw = "# echo\nprogram: 3,0,4,0,99\ninput: 1\noutput: 1\nmemory: 1,0\ninput: 2\n";
+    let case = Case::parse("echo", raw);
+
+    assert_eq!(vec![3, 0, 4, 0, 99], case.programm);
+    assert_eq!(2, case.runs.len());
+    assert_eq!(Some(vec![1, 0]), case.runs[0].memory);
+    assert_eq!(vec![2], case.runs[1].input);
+    assert_eq!(None, case.runs[1].output);
+}